@@ -5,6 +5,7 @@ use std::{collections::HashMap, env, fmt, fs, str::FromStr};
 pub type Span = std::ops::Range<usize>;
 pub type Spanned<T> = (T, Span);
 
+#[derive(Debug, Clone)]
 pub struct Error {
     pub span: Span,
     pub msg: String,
@@ -29,45 +30,186 @@ pub enum Token {
     As,
     If,
     Else,
+    Extern,
+    While,
+    For,
+    Break,
+    Continue,
+    New,
+    Delete,
+    Static,
+    Union,
+    Is,
+    Match,
 } 
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Type {
     Void,
     Bool,
+    Char,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    USize,
     F32,
+    F64,
     Str,
+    Pointer(Box<Type>),
+    // a name that is not a primitive type. resolved by the type checker
+    Named(String),
+    // a generic class given its type arguments, as in List<i32>. it is given the name of the class's
+    // instance for those types before the type checker sees it, and so becomes a Named type
+    Generic(String, Vec<Type>),
+    // a function value, which is either a function or a closure, given the types of its parameters and what it returns
+    Function(Vec<Type>, Box<Type>),
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Type {
+        match name {
+            "void" => Type::Void,
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "usize" => Type::USize,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "str" => Type::Str,
+            _ => Type::Named(name.to_string()),
+        }
+    }
+
+    pub fn is_int(&self) -> bool {
+        self.int_width().is_some()
+    }
+
+    /// The width of an integer type in bits, and whether it is signed.
+    /// usize depends on the target, so literals are checked as if it were 64 bits wide
+    pub fn int_width(&self) -> Option<(u32, bool)> {
+        match self {
+            Type::I8 => Some((8, true)),
+            Type::I16 => Some((16, true)),
+            Type::I32 => Some((32, true)),
+            Type::I64 => Some((64, true)),
+            Type::U8 => Some((8, false)),
+            Type::U16 => Some((16, false)),
+            Type::U32 => Some((32, false)),
+            Type::U64 | Type::USize => Some((64, false)),
+            _ => None,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self.int_width(), Some((_, true))) || self.is_float()
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_int() || self.is_float()
+    }
+
+    /// Replaces the type parameters of a generic function or class that appear in a type with the types they stand for.
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Named(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Pointer(pointee) => Type::Pointer(Box::new(pointee.substitute(bindings))),
+            Type::Generic(name, type_args) => {
+                Type::Generic(name.clone(), type_args.iter().map(|typ| typ.substitute(bindings)).collect())
+            },
+            Type::Function(params, return_type) => {
+                Type::Function(params.iter().map(|typ| typ.substitute(bindings)).collect(), Box::new(return_type.substitute(bindings)))
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Turns the generic classes in a type into the names of their instances, as List<i32> into
+    /// the class named List<i32>, and records each instance that is used, along with its type arguments.
+    pub fn name_instances(&self, used: &mut Vec<(String, Vec<Type>)>) -> Type {
+        match self {
+            Type::Generic(name, type_args) => {
+                let type_args: Vec<Type> = type_args.iter().map(|typ| typ.name_instances(used)).collect();
+                let symbol = instance_symbol(name, &type_args);
+                used.push((name.clone(), type_args));
+                Type::Named(symbol)
+            },
+            Type::Pointer(pointee) => Type::Pointer(Box::new(pointee.name_instances(used))),
+            Type::Function(params, return_type) => {
+                Type::Function(params.iter().map(|typ| typ.name_instances(used)).collect(), Box::new(return_type.name_instances(used)))
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::USize => write!(f, "usize"),
+            Type::F32 => write!(f, "f32"),
+            Type::F64 => write!(f, "f64"),
+            Type::Str => write!(f, "str"),
+            Type::Pointer(pointee) => write!(f, "{}*", pointee),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Generic(name, type_args) => write!(f, "{}", instance_symbol(name, type_args)),
+            Type::Function(params, return_type) => {
+                let params: Vec<String> = params.iter().map(|typ| typ.to_string()).collect();
+                match **return_type {
+                    Type::Void => write!(f, "fn({})", params.join(", ")),
+                    _ => write!(f, "fn({}) -> {}", params.join(", "), return_type),
+                }
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Null,
     Bool(bool),
-    Num(f32),
+    // number literals keep their suffix, if they have one. the type checker
+    // fills in the type of the ones that don't
+    Int(u64, Option<Type>),
+    Float(f64, Option<Type>),
     Str(String),
     List(Vec<Value>),
     Func(String),
 }
 
-impl Value {
-    pub fn num(self, span: Span) -> Result<f32, Error> {
-        if let Value::Num(x) = self {
-            Ok(x)
-        } else {
-            Err(Error {
-                span,
-                msg: format!("'{}' is not a number", self),
-            })
-        }
-    }
-}
-
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Null => write!(f, "null"),
             Self::Bool(x) => write!(f, "{}", x),
-            Self::Num(x) => write!(f, "{}", x),
+            Self::Int(x, _) => write!(f, "{}", x),
+            Self::Float(x, _) => write!(f, "{}", x),
             Self::Str(x) => write!(f, "{}", x),
             Self::List(xs) => write!(
                 f,
@@ -82,7 +224,7 @@ impl std::fmt::Display for Value {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -90,6 +232,21 @@ pub enum BinaryOp {
     Div,
     Eq,
     NotEq,
+    Lt,
+    Gt,
+    LtEq,
+    GtEq,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+    // taking the address of something, and reading what a pointer points to
+    Ref,
+    Deref,
 }
 
 #[derive(Debug, Clone)]
@@ -98,22 +255,188 @@ pub enum Expr {
     Value(Value),
     List(Vec<Spanned<Self>>),
     Local(String),
-    Var(String, String, Box<Spanned<Self>>),
+    // the type is optional in the source, and filled in by the type checker, with the span of the value it comes from.
+    // without a value, the variable is given its type's default value
+    Var(String, Option<Spanned<Type>>, Option<Box<Spanned<Self>>>),
     Then(Box<Spanned<Self>>, Box<Spanned<Self>>),
-    Binary(Box<Spanned<Self>>, BinaryOp, Box<Spanned<Self>>),
+    Block(Box<Spanned<Self>>),
+    // the type of the operands is filled in by the type checker, so that signedness is known
+    Binary(Box<Spanned<Self>>, BinaryOp, Box<Spanned<Self>>, Option<Type>),
+    Unary(UnaryOp, Box<Spanned<Self>>),
+    // the type being cast from is filled in by the type checker
+    Cast(Box<Spanned<Self>>, Type, Option<Type>),
+    // compound assignments are turned into a binary op on the target's value by the parser
+    Assign(Box<Spanned<Self>>, Box<Spanned<Self>>),
+    // the value held by the target of the compound assignment it is in, as the a in a += b.
+    // the target itself is only evaluated once, by the assignment
+    AssignedValue,
     Call(Box<Spanned<Self>>, Vec<Spanned<Self>>),
+    // the name of a generic function given its type arguments, as the f<i32> in f<i32>(x).
+    // the type checker turns it into the name of the instance of the function for those types
+    GenericName(String, Vec<Type>),
+    // reading one of the bytes of a string, as in name[0]
+    Index(Box<Spanned<Self>>, Box<Spanned<Self>>),
+    // the index of the field in its class's struct is filled in by the type checker
+    Field(Box<Spanned<Self>>, String, Option<usize>),
+    // the type that the called method belongs to is filled in by the type checker.
+    // it can be a base class of the object's type, if the method is inherited
+    MethodCall(Box<Spanned<Self>>, String, Vec<Spanned<Self>>, Option<Type>),
+    // creating an instance of a class by calling one of its constructors, as in Base(5).
+    // calls of a class's name are turned into this by the type checker, which also fills in
+    // the symbol of the constructor. classes without constructors have no symbol
+    Construct(Type, Vec<Spanned<Self>>, Option<String>),
+    // creating an instance of a class on the heap, as in new Base(5).
+    // the expression inside constructs the instance, and is turned into a Construct by the type checker
+    New(Box<Spanned<Self>>),
+    // destroying an object on the heap and freeing its memory.
+    // the type of the object being pointed to is filled in by the type checker
+    Delete(Box<Spanned<Self>>, Option<Type>),
+    // printing the arguments, as in println("x is {0}", x), and a newline after them if the bool is set.
+    // calls of print and println are turned into this by the type checker, which also takes the format
+    // string out of the arguments and splits it into what it prints
+    Print(Vec<Spanned<Self>>, Vec<FormatPart>, bool),
+    // creating a value of one of a union's variants, as in Shape.Circle(2.0), from the values it holds.
+    // uses of a union's variants are turned into this by the type checker, which also fills in the variant's tag
+    Variant(Type, String, Vec<Spanned<Self>>, Option<usize>),
+    // whether a union holds the named variant, as in shape is Circle. the variant's tag is filled in by the type checker
+    Is(Box<Spanned<Self>>, String, Option<usize>),
+    // running the first arm whose patterns match a value, as in match shape { Circle(r) => r; _ => 0.0; }.
+    // the type of the value being matched is filled in by the type checker
+    Match(Box<Spanned<Self>>, Vec<MatchArm>, Option<Type>),
+    // a function written inside another, which can use the variables it captures from it
+    Lambda(Box<Lambda>),
+    // a static field of a type, which is a global rather than part of any instance.
+    // uses of static fields are turned into this by the type checker
+    StaticField(Type, String),
     If(Box<Spanned<Self>>, Box<Spanned<Self>>, Box<Spanned<Self>>),
+    While(Box<Spanned<Self>>, Box<Spanned<Self>>),
+    For(ForHeader, Box<Spanned<Self>>),
+    Break,
+    Continue,
     Ret(Box<Spanned<Self>>),
 }
 
+/// The three forms a for loop can take
+#[derive(Debug, Clone)]
+pub enum ForHeader {
+    // for var i = 0 : i < length : i += 2
+    // a var declaration, a condition and a step
+    Standard(Box<Spanned<Expr>>, Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    // for var i : 0..length
+    // the variable and its optional type, the start and end of the range, and an optional step
+    Range(String, Option<Spanned<Type>>, Box<Spanned<Expr>>, Box<Spanned<Expr>>, Option<Box<Spanned<Expr>>>),
+    // for var item : collection
+    Each(String, Box<Spanned<Expr>>),
+}
+
+/// A variable of the enclosing function that a closure uses, either as a copy of its value, or by reference.
+/// its type is filled in by the type checker
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub name: String,
+    pub by_reference: bool,
+    pub typ: Option<Type>,
+    pub span: Span,
+}
+
+/// A closure, as in fn[count, &total](x as i32) -> i32 { x * count }. it is compiled into a function of its own,
+/// which is given the captured variables in an environment alongside its parameters
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub captures: Vec<Capture>,
+    pub params: Vec<(String, Type)>,
+    pub return_type: Type,
+    pub body: Spanned<Expr>,
+}
+
+/// Something that a value of a match can be compared against
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // _, which matches anything
+    Wildcard,
+    // a name, which matches anything and holds what it matched in the arm.
+    // names of the variants of a union being matched are turned into variant patterns by the type checker
+    Binding(String),
+    // an integer or bool literal, which may be negated
+    Literal(Box<Spanned<Expr>>),
+    // one of a union's variants, as in Rect(w, _), along with patterns for the values it holds.
+    // the variant's tag is filled in by the type checker
+    Variant(String, Vec<Spanned<Pattern>>, Option<usize>),
+}
+
+/// One arm of a match, which runs its body if any of its patterns match, as in 1, 2 => body;
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub patterns: Vec<Spanned<Pattern>>,
+    pub body: Spanned<Expr>,
+}
+
+/// A piece of a format string, as in "x is {0}"
+#[derive(Debug, Clone)]
+pub enum FormatPart {
+    // text that is printed as it is
+    Text(String),
+    // one of the arguments, by its position. its type is filled in by the type checker
+    Arg(usize, Option<Type>),
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionSignature {
-    pub return_type: String,
-    pub params: Vec<(String, String)>,
+    pub return_type: Type,
+    // where the return type is written, for errors about it
+    pub return_span: Span,
+    // no return type is written, so it is inferred from the function's returns before anything is checked.
+    // until it is, the return type is void
+    pub infers_return: bool,
+    pub params: Vec<(String, Type)>,
+    // where the type of each parameter is written, for errors about it
+    pub param_spans: Vec<Span>,
     pub generic_params: Vec<String>,
+    // where each type parameter is written, for errors about it
+    pub generic_param_spans: Vec<Span>,
+    pub span: Span,
+    // the type a method belongs to. methods take a pointer to it as their first parameter, 'this'
+    pub owner: Option<Type>,
+    // static methods belong to a type, but are not given an instance of it
+    pub is_static: bool,
     //body: Expr,
 }
 
+impl FunctionSignature {
+    /// Turns the signature into that of a method of the given type.
+    /// static methods don't get a 'this' parameter
+    pub fn into_method(mut self, owner: Type) -> Self {
+        if !self.is_static {
+            self.params.insert(0, ("this".to_string(), Type::Pointer(Box::new(owner.clone()))));
+            self.param_spans.insert(0, self.span.clone());
+        }
+        self.owner = Some(owner);
+        self
+    }
+}
+
+/// The name a static field is known by in the module.
+pub fn static_field_symbol(owner: &Type, name: &str) -> String {
+    format!("{}.{}", owner, name)
+}
+
+/// The name a method is known by in the module. it is prefixed with the type the method
+/// belongs to, so that different types can have methods with the same name
+pub fn method_symbol(owner: &Type, name: &str) -> String {
+    format!("{}.{}", owner, name)
+}
+
+/// The name a constructor is known by in the module. a type can have several constructors,
+/// which are told apart by how many parameters they take
+pub fn constructor_symbol(owner: &Type, arity: usize) -> String {
+    format!("{}.{}", method_symbol(owner, "new"), arity)
+}
+
+/// The name a destructor is known by in the module. a type has at most one
+pub fn destructor_symbol(owner: &Type) -> String {
+    method_symbol(owner, "delete")
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionDefinition {
     pub signature: FunctionSignature,
@@ -126,15 +449,368 @@ pub struct NamedFunction {
     pub definition: FunctionDefinition
 }
 
+impl NamedFunction {
+    /// The name the function is known by in the module.
+    /// constructors and destructors are named after the keywords that declare them
+    pub fn symbol(&self) -> String {
+        let signature = &self.definition.signature;
+        match &signature.owner {
+            Some(owner) if self.name == "new" => constructor_symbol(owner, signature.params.len() - 1),
+            Some(owner) if self.name == "delete" => destructor_symbol(owner),
+            Some(owner) => method_symbol(owner, &self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Makes the instance of a generic function for the given type arguments, which is an ordinary
+    /// function with the type parameters replaced by the types they stand for.
+    pub fn instantiate(&self, type_args: &[Type]) -> NamedFunction {
+        let bindings: HashMap<String, Type> = self.definition.signature.generic_params
+            .iter()
+            .cloned()
+            .zip(type_args.iter().cloned())
+            .collect();
+
+        let mut instance = self.substitute(&bindings);
+        instance.definition.signature.generic_params.clear();
+        instance.definition.signature.generic_param_spans.clear();
+        instance.name = instance_symbol(&self.name, type_args);
+        instance
+    }
+
+    /// Replaces type parameters throughout the function, including the type it belongs to if it is a method.
+    fn substitute(&self, bindings: &HashMap<String, Type>) -> NamedFunction {
+        let mut definition = self.definition.clone();
+        let signature = &mut definition.signature;
+        signature.return_type = signature.return_type.substitute(bindings);
+        for (_name, param_type) in signature.params.iter_mut() {
+            *param_type = param_type.substitute(bindings);
+        }
+        signature.owner = signature.owner.as_ref().map(|owner| owner.substitute(bindings));
+        substitute_types(&mut definition.body, bindings);
+
+        NamedFunction {
+            name: self.name.clone(),
+            definition,
+        }
+    }
+
+    /// Turns the generic classes the function uses into their instances. see `Type::name_instances`
+    fn name_instances(&mut self, class_templates: &HashMap<String, ClassTemplate>, used: &mut Vec<(String, Vec<Type>)>) {
+        let signature = &mut self.definition.signature;
+        signature.return_type = signature.return_type.name_instances(used);
+        for (_name, param_type) in signature.params.iter_mut() {
+            *param_type = param_type.name_instances(used);
+        }
+        signature.owner = signature.owner.as_ref().map(|owner| owner.name_instances(used));
+        name_instances_in(&mut self.definition.body, class_templates, used);
+    }
+}
+
+/// The name an instance of a generic function is known by, which lists the types it is for, as in max<i32>.
+pub fn instance_symbol(name: &str, type_args: &[Type]) -> String {
+    let type_args: Vec<String> = type_args.iter().map(|typ| typ.to_string()).collect();
+    format!("{}<{}>", name, type_args.join(", "))
+}
+
+/// Calls the given function on an expression and then on every expression inside it, outermost first.
+pub fn visit_exprs(expr: &mut Spanned<Expr>, f: &mut impl FnMut(&mut Spanned<Expr>)) {
+    f(expr);
+
+    match &mut expr.0 {
+        Expr::Error | Expr::Value(_) | Expr::Local(_) | Expr::AssignedValue | Expr::Break | Expr::Continue
+            | Expr::GenericName(_, _) | Expr::StaticField(_, _) => {},
+        Expr::List(items) | Expr::Print(items, _, _) | Expr::Construct(_, items, _) | Expr::Variant(_, _, items, _) => {
+            for item in items.iter_mut() {
+                visit_exprs(item, f);
+            }
+        },
+        Expr::Var(_, _, val) => {
+            if let Some(val) = val {
+                visit_exprs(val, f);
+            }
+        },
+        Expr::Then(a, b) | Expr::Assign(a, b) | Expr::Index(a, b) | Expr::While(a, b) | Expr::Binary(a, _, b, _) => {
+            visit_exprs(a, f);
+            visit_exprs(b, f);
+        },
+        Expr::Block(a) | Expr::Unary(_, a) | Expr::Field(a, _, _) | Expr::New(a) | Expr::Delete(a, _)
+            | Expr::Ret(a) | Expr::Cast(a, _, _) | Expr::Is(a, _, _) => {
+            visit_exprs(a, f);
+        },
+        Expr::Call(a, args) | Expr::MethodCall(a, _, args, _) => {
+            visit_exprs(a, f);
+            for arg in args.iter_mut() {
+                visit_exprs(arg, f);
+            }
+        },
+        Expr::If(cond, a, b) => {
+            visit_exprs(cond, f);
+            visit_exprs(a, f);
+            visit_exprs(b, f);
+        },
+        Expr::Match(a, arms, _) => {
+            visit_exprs(a, f);
+            for arm in arms.iter_mut() {
+                visit_exprs(&mut arm.body, f);
+            }
+        },
+        Expr::Lambda(lambda) => visit_exprs(&mut lambda.body, f),
+        Expr::For(header, body) => {
+            match header {
+                ForHeader::Standard(init, cond, step) => {
+                    visit_exprs(init, f);
+                    visit_exprs(cond, f);
+                    visit_exprs(step, f);
+                },
+                ForHeader::Range(_, _, start, end, step) => {
+                    visit_exprs(start, f);
+                    visit_exprs(end, f);
+                    if let Some(step) = step {
+                        visit_exprs(step, f);
+                    }
+                },
+                ForHeader::Each(_, collection) => visit_exprs(collection, f),
+            }
+            visit_exprs(body, f);
+        },
+    }
+}
+
+/// The types written in an expression itself, such as in var declarations and casts,
+/// not including those of the expressions inside it or the ones filled in by the type checker.
+fn written_types(expr: &mut Expr) -> Vec<&mut Type> {
+    match expr {
+        Expr::Var(_, Some((typ, _)), _) | Expr::Cast(_, typ, _) | Expr::Construct(typ, _, _) | Expr::StaticField(typ, _)
+            | Expr::Variant(typ, _, _, _) => vec![typ],
+        Expr::For(ForHeader::Range(_, Some((typ, _)), _, _, _), _) => vec![typ],
+        Expr::GenericName(_, type_args) => type_args.iter_mut().collect(),
+        Expr::Lambda(lambda) => lambda.params
+            .iter_mut()
+            .map(|(_, typ)| typ)
+            .chain(std::iter::once(&mut lambda.return_type))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces the type parameters of a generic function or class that are written in an expression with the types they stand for.
+pub fn substitute_types(expr: &mut Spanned<Expr>, bindings: &HashMap<String, Type>) {
+    visit_exprs(expr, &mut |expr| {
+        for typ in written_types(&mut expr.0) {
+            *typ = typ.substitute(bindings);
+        }
+    });
+}
+
+/// Turns the generic classes written in an expression into their instances. a generic class given
+/// type arguments where a function's name would go, as in List<i32>(), names its instance too
+fn name_instances_in(expr: &mut Spanned<Expr>, class_templates: &HashMap<String, ClassTemplate>, used: &mut Vec<(String, Vec<Type>)>) {
+    visit_exprs(expr, &mut |expr| {
+        for typ in written_types(&mut expr.0) {
+            *typ = typ.name_instances(used);
+        }
+
+        if let Expr::GenericName(name, type_args) = &expr.0 {
+            if class_templates.contains_key(name) {
+                used.push((name.clone(), type_args.clone()));
+                expr.0 = Expr::Local(instance_symbol(name, type_args));
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternFunction {
+    pub name: String,
+    pub signature: FunctionSignature,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub typ: Type,
+    // assigned to the field whenever an instance of the class is created
+    pub initializer: Option<Spanned<Expr>>,
+    // a static field is shared by the whole class, rather than being part of each instance
+    pub is_static: bool,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct Class {
     pub name: String,
+    // the class this one derives from, whose fields and methods it inherits
+    pub base: Option<String>,
+    // the type parameters of a generic class, as the T in List<T>
+    pub generic_params: Vec<String>,
+    // where each type parameter is written, for errors about it
+    pub generic_param_spans: Vec<Span>,
+    // the generic class that this one is an instance of, and the types its type parameters stand for
+    pub instance_of: Option<(String, Vec<Type>)>,
+    // the fields declared in the class itself, not including inherited ones
+    pub fields: Vec<Field>,
+    // the names of the methods declared in the class, in order.
+    // constructors, destructors and static methods are not included
+    pub methods: Vec<String>,
+    pub span: Span,
+}
+
+impl Class {
+    /// The fields that are part of each instance of the class, as opposed to static ones.
+    pub fn instance_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|field| !field.is_static)
+    }
+}
+
+/// One of the forms that a union's value can take, and the types of the values it holds.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub payload: Vec<Type>,
+    pub span: Span,
+}
+
+/// A tagged union, whose value is one of its variants. the tag says which, and is the index of the variant
+#[derive(Debug, Clone)]
+pub struct Union {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+impl Union {
+    /// Finds a variant by its name, along with its tag.
+    pub fn variant(&self, name: &str) -> Option<(usize, &Variant)> {
+        self.variants.iter().enumerate().find(|(_, variant)| variant.name == name)
+    }
+}
+
+/// A generic class, along with the functions that belong to it. it is not checked or compiled itself,
+/// only the instances of it that the program uses are, which are made for each set of type arguments
+#[derive(Debug, Clone)]
+pub struct ClassTemplate {
+    pub class: Class,
+    pub functions: Vec<NamedFunction>,
+}
+
+impl ClassTemplate {
+    /// Makes the instance of the class for the given type arguments, along with its methods, constructors and destructor.
+    /// inside the class, its name on its own refers to the instance
+    pub fn instantiate(&self, type_args: &[Type]) -> (Class, Vec<NamedFunction>) {
+        let symbol = instance_symbol(&self.class.name, type_args);
+        let mut bindings: HashMap<String, Type> = self.class.generic_params
+            .iter()
+            .cloned()
+            .zip(type_args.iter().cloned())
+            .collect();
+        bindings.insert(self.class.name.clone(), Type::Named(symbol.clone()));
+
+        let mut class = self.class.clone();
+        class.name = symbol;
+        class.generic_params.clear();
+        class.generic_param_spans.clear();
+        class.instance_of = Some((self.class.name.clone(), type_args.to_vec()));
+        for field in class.fields.iter_mut() {
+            field.typ = field.typ.substitute(&bindings);
+            if let Some(initializer) = &mut field.initializer {
+                substitute_types(initializer, &bindings);
+            }
+        }
+
+        let functions = self.functions.iter().map(|function| function.substitute(&bindings)).collect();
+        (class, functions)
+    }
+}
+
+/// A class followed by its base classes, nearest first.
+/// the chain stops at a base class that doesn't exist, or one that has already been seen
+pub fn class_ancestry<'c>(classes: &'c HashMap<String, Class>, class_name: &str) -> Vec<&'c Class> {
+    let mut ancestry: Vec<&Class> = Vec::new();
+    let mut next = classes.get(class_name);
+
+    while let Some(class) = next {
+        if ancestry.iter().any(|seen| seen.name == class.name) {
+            break;
+        }
+
+        ancestry.push(class);
+        next = class.base.as_ref().and_then(|base| classes.get(base));
+    }
+
+    ancestry
+}
+
+/// Every field of a class, including the ones it inherits, in the order they are laid out.
+/// the fields of a base class come first, so a derived class starts with the same layout as its base
+pub fn all_fields<'c>(classes: &'c HashMap<String, Class>, class_name: &str) -> Vec<&'c Field> {
+    class_ancestry(classes, class_name)
+        .into_iter()
+        .rev()
+        .flat_map(|class| class.instance_fields())
+        .collect()
+}
+
+/// Whether a class is part of an inheritance hierarchy. such classes start with a pointer to a vtable,
+/// so that calling a method through a pointer to a base class calls the version of the object's own class
+pub fn has_vtable(classes: &HashMap<String, Class>, class_name: &str) -> bool {
+    classes.get(class_name).is_some_and(|class| class.base.is_some())
+        || classes.values().any(|class| class.base.as_deref() == Some(class_name))
+}
+
+/// The index of a class's first field in its struct, which comes after the vtable pointer if there is one.
+pub fn first_field_index(classes: &HashMap<String, Class>, class_name: &str) -> usize {
+    has_vtable(classes, class_name) as usize
 }
 
 #[derive(Debug, Clone)]
 pub enum ProgramUnit {
     Class(Class),
+    Union(Union),
     Function(NamedFunction),
+    Extern(ExternFunction),
+}
+
+impl ProgramUnit {
+    /// Where the unit is declared, for errors about it as a whole.
+    pub fn span(&self) -> &Span {
+        match self {
+            ProgramUnit::Class(class) => &class.span,
+            ProgramUnit::Union(union) => &union.span,
+            ProgramUnit::Function(function) => &function.definition.signature.span,
+            ProgramUnit::Extern(function) => &function.signature.span,
+        }
+    }
+
+    /// Turns the generic classes that the unit uses into the names of their instances, and records each
+    /// instance that is used. a unit has to be given this before it is checked
+    pub fn name_instances(&mut self, class_templates: &HashMap<String, ClassTemplate>, used: &mut Vec<(String, Vec<Type>)>) {
+        match self {
+            ProgramUnit::Class(class) => {
+                for field in class.fields.iter_mut() {
+                    field.typ = field.typ.name_instances(used);
+                    if let Some(initializer) = &mut field.initializer {
+                        name_instances_in(initializer, class_templates, used);
+                    }
+                }
+            },
+            ProgramUnit::Union(union) => {
+                for variant in union.variants.iter_mut() {
+                    for typ in variant.payload.iter_mut() {
+                        *typ = typ.name_instances(used);
+                    }
+                }
+            },
+            ProgramUnit::Function(function) => function.name_instances(class_templates, used),
+            ProgramUnit::Extern(function) => {
+                let signature = &mut function.signature;
+                signature.return_type = signature.return_type.name_instances(used);
+                for (_name, param_type) in signature.params.iter_mut() {
+                    *param_type = param_type.name_instances(used);
+                }
+            },
+        }
+    }
 }
 
 impl fmt::Display for Token {
@@ -154,6 +830,17 @@ impl fmt::Display for Token {
             Token::Ret => write!(f, "return"),
             Token::If => write!(f, "if"),
             Token::Else => write!(f, "else"),
+            Token::Extern => write!(f, "extern"),
+            Token::While => write!(f, "while"),
+            Token::For => write!(f, "for"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::New => write!(f, "new"),
+            Token::Delete => write!(f, "delete"),
+            Token::Static => write!(f, "static"),
+            Token::Union => write!(f, "union"),
+            Token::Is => write!(f, "is"),
+            Token::Match => write!(f, "match"),
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 use crate::Value;
@@ -8,30 +9,102 @@ use crate::FunctionDefinition;
 use crate::Error;
 use crate::BinaryOp;
 use crate::ast::FunctionSignature;
+use crate::ast::Type;
+use crate::ast::ForHeader;
+use crate::ast::FormatPart;
+use crate::ast::Class;
+use crate::ast::Union;
+use crate::ast::Variant;
+use crate::ast::Pattern;
+use crate::ast::Lambda;
+use crate::ast::UnaryOp;
+use crate::ast::constructor_symbol;
+use crate::ast::destructor_symbol;
+use crate::ast::method_symbol;
+use crate::ast::static_field_symbol;
+use crate::ast::class_ancestry;
+use crate::ast::all_fields;
+use crate::ast::has_vtable;
+use crate::ast::first_field_index;
+use crate::ast::Span;
 
 
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
 use inkwell::execution_engine::{ExecutionEngine, JitFunction};
 use inkwell::module::Linkage;
 use inkwell::module::Module;
+use inkwell::targets::TargetData;
 
 use inkwell::types::*;
 
 
-use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, BasicValue, IntValue, FloatValue, FunctionValue, PointerValue};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, BasicValue, CallableValue, IntValue, FloatValue, FunctionValue, PointerValue, StructValue};
 
 use inkwell::OptimizationLevel;
+use inkwell::AddressSpace;
 use inkwell::FloatPredicate;
+use inkwell::IntPredicate;
 
 
 use std::error::Error as InkwellError;
 
 
+/// LLVM's identifier for the C calling convention.
+const C_CALLING_CONVENTION: u32 = 0;
+
+/// The name of a class's vtable, and of the struct type it has.
+fn vtable_symbol(class_name: &str) -> String {
+    format!("{}.vtable", class_name)
+}
+
+/// The expression that gives another its value, such as the last expression of a block.
+/// an object made by it is moved to wherever the other's value goes
+fn result_of(expr: &Spanned<Expr>) -> &Spanned<Expr> {
+    match &expr.0 {
+        Expr::Block(body) => result_of(body),
+        Expr::Then(_, b) => result_of(b),
+        _ => expr,
+    }
+}
+
+/// Whether a pattern only tests the literal or variant that a value starts with,
+/// so that a switch can jump to its arm
+fn is_flat_pattern(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Variant(_, fields, _) => fields.iter().all(|field| matches!(field.0, Pattern::Wildcard | Pattern::Binding(_))),
+        _ => true,
+    }
+}
+
+/// The first slot of every vtable, which holds a function that destroys the whole object.
+/// it can't clash with a method, as delete is a keyword
+const DESTROY_SLOT: &str = "delete.all";
+
 pub struct Compiler<'a, 'ctx> {
     pub context: &'ctx Context,
     pub builder: &'a Builder<'ctx>,
     pub module:  &'a Module<'ctx>,
+    // usize is as wide as a pointer on the target
+    pub usize_type: IntType<'ctx>,
+    // every class in the program, by name. their struct types are in the module
+    pub classes: RefCell<HashMap<String, Class>>,
+    // every union in the program, by name. their struct types are in the module too
+    pub unions: RefCell<HashMap<String, Union>>,
+    // the blocks that continue and break jump to, for each loop being compiled,
+    // and how many scopes and temporaries there are outside of the loop
+    pub loop_stack: RefCell<Vec<(BasicBlock<'ctx>, BasicBlock<'ctx>, usize, usize)>>,
+    // the objects that need destroying in each scope being compiled, in the order they were declared,
+    // along with whether each is still in its variable, as it isn't once it has been moved out
+    pub scope_stack: RefCell<Vec<Vec<(PointerValue<'ctx>, Type, PointerValue<'ctx>)>>>,
+    // the memory being assigned to by each assignment being compiled, which compound assignments read from
+    pub assigned_places: RefCell<Vec<PointerValue<'ctx>>>,
+    // the objects made by the statements being compiled that nothing took, which are destroyed at the end
+    // of the statement they were made in, and the expressions they were made by
+    pub temporaries: RefCell<Vec<(PointerValue<'ctx>, Type, Span)>>,
+    // the expressions whose objects are being moved into a variable or out of a function, so aren't temporaries
+    pub moved: RefCell<Vec<Span>>,
 }
 
 
@@ -48,56 +121,964 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
             None => builder.position_at_end(entry),
         }
 
-        builder.build_alloca(var_type, name)
+        builder.build_alloca(var_type, name)
+    }
+
+    /// Maps a type that holds a value to its LLVM type.
+    /// the type checker has already rejected unknown names and void values.
+    pub fn to_type(&self, typ: &Type) -> BasicTypeEnum<'ctx> {
+        match typ {
+            Type::Bool => self.context.bool_type().into(),
+            Type::F32 => self.context.f32_type().into(),
+            Type::F64 => self.context.f64_type().into(),
+            // integers don't carry their signedness in LLVM, the instructions that use them do
+            Type::Char | Type::I8 | Type::U8 => self.context.i8_type().into(),
+            Type::I16 | Type::U16 => self.context.i16_type().into(),
+            Type::I32 | Type::U32 => self.context.i32_type().into(),
+            Type::I64 | Type::U64 => self.context.i64_type().into(),
+            Type::USize => self.usize_type.into(),
+            Type::Str => self.str_type().into(),
+            Type::Pointer(pointee) => self.to_type(pointee).ptr_type(AddressSpace::Generic).into(),
+            Type::Named(name) => self.module.get_struct_type(name).unwrap().into(),
+            // a function value is a pointer to its code, and one to the environment of the closure, which the code is given
+            Type::Function(params, return_type) => {
+                let code_type = self.closure_fn_type(params, return_type).ptr_type(AddressSpace::Generic);
+                self.context.struct_type(&[code_type.into(), self.env_type().into()], false).into()
+            },
+            _ => unreachable!("{} has no value representation", typ),
+        }
+    }
+
+    /// The type of strings: a pointer to their bytes, and how many bytes there are.
+    pub fn str_type(&self) -> StructType<'ctx> {
+        self.context.struct_type(&[self.context.i8_type().ptr_type(AddressSpace::Generic).into(), self.usize_type.into()], false)
+    }
+
+    /// The type of the environment of a closure, as its code is given it. functions that aren't closures have none
+    fn env_type(&self) -> PointerType<'ctx> {
+        self.context.i8_type().ptr_type(AddressSpace::Generic)
+    }
+
+    /// The LLVM function type of the code of a function value, which is given the environment of the closure before its parameters.
+    fn closure_fn_type(&self, params: &[Type], return_type: &Type) -> FunctionType<'ctx> {
+        let param_types: Vec<BasicMetadataTypeEnum> = std::iter::once(self.env_type().into())
+            .chain(params.iter().map(|param_type| self.to_type(param_type).into()))
+            .collect();
+
+        match return_type {
+            Type::Void => self.context.void_type().fn_type(param_types.as_slice(), false),
+            return_type => self.to_type(return_type).fn_type(param_types.as_slice(), false),
+        }
+    }
+
+    /// A function that calls the named one, and is given an environment first, as the code of a closure is,
+    /// so that the function can be used as a function value. it ignores the environment
+    fn function_thunk(&self, name: &str, signature: &FunctionSignature) -> FunctionValue<'ctx> {
+        let symbol = format!("{}.thunk", name);
+        if let Some(thunk) = self.module.get_function(&symbol) {
+            return thunk;
+        }
+
+        let params: Vec<Type> = signature.params.iter().map(|(_, param_type)| param_type.clone()).collect();
+        let thunk = self.module.add_function(&symbol, self.closure_fn_type(&params, &signature.return_type), None);
+
+        // the thunk is built on its own, as the builder is in the middle of another function
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(thunk, "entry"));
+
+        let args: Vec<BasicMetadataValueEnum> = thunk.get_param_iter().skip(1).map(|param| param.into()).collect();
+        let result = builder.build_call(self.module.get_function(name).unwrap(), args.as_slice(), "call").try_as_basic_value().left();
+        match result {
+            Some(result) => builder.build_return(Some(&result)),
+            None => builder.build_return(None),
+        };
+
+        thunk
+    }
+
+    /// Builds the LLVM function type described by a signature.
+    pub fn to_fn_type(&self, signature: &FunctionSignature) -> FunctionType<'ctx> {
+        let param_types: Vec<BasicMetadataTypeEnum> = signature.params
+            .iter()
+            .map(|(_name, param_type)| Into::<BasicMetadataTypeEnum>::into(self.to_type(param_type)))
+            .collect::<Vec<BasicMetadataTypeEnum>>();
+
+        match &signature.return_type {
+            Type::Void => self.context.void_type().fn_type(param_types.as_slice(), false),
+            return_type => self.to_type(return_type).fn_type(param_types.as_slice(), false),
+        }
+    }
+
+    /// Adds a named struct type for a class to the module. its fields are filled in
+    /// by `define_class`, once every class has been declared and can be used as a field.
+    pub fn declare_class(&self, class: &Class) {
+        self.context.opaque_struct_type(&class.name);
+        self.classes.borrow_mut().insert(class.name.clone(), class.clone());
+    }
+
+    /// Lays out a class's struct type: a pointer to its vtable if it has one, then the fields
+    /// it inherits, then its own fields in the order they were declared.
+    pub fn define_class(&self, class: &Class) {
+        let classes = self.classes.borrow();
+
+        let mut field_types: Vec<BasicTypeEnum> = Vec::new();
+        if has_vtable(&classes, &class.name) {
+            field_types.push(self.context.i8_type().ptr_type(AddressSpace::Generic).into());
+        }
+
+        field_types.extend(all_fields(&classes, &class.name).iter().map(|field| self.to_type(&field.typ)));
+
+        self.module.get_struct_type(&class.name).unwrap().set_body(field_types.as_slice(), false);
+    }
+
+    /// Adds a named struct type for a union to the module. it is laid out by `define_unions`,
+    /// once every class is, as the variants of a union can hold instances of any class.
+    pub fn declare_union(&self, union: &Union) {
+        self.context.opaque_struct_type(&union.name);
+        self.unions.borrow_mut().insert(union.name.clone(), union.clone());
+    }
+
+    /// The type of a union's tag, which is the index of the variant it holds.
+    fn tag_type(&self) -> IntType<'ctx> {
+        self.context.i32_type()
+    }
+
+    /// The struct type of the values that a variant of a union holds.
+    fn payload_type(&self, variant: &Variant) -> StructType<'ctx> {
+        let field_types: Vec<BasicTypeEnum> = variant.payload.iter().map(|typ| self.to_type(typ)).collect();
+        self.context.struct_type(field_types.as_slice(), false)
+    }
+
+    /// A pointer to the values that a union in memory holds as one of its variants, along with the variant.
+    fn payload_pointer(&self, union: PointerValue<'ctx>, typ: &Type, tag: usize) -> (PointerValue<'ctx>, Variant) {
+        let variant = self.unions.borrow()[&typ.to_string()].variants[tag].clone();
+        let buffer = self.builder.build_struct_gep(union, 2, "buffer").unwrap();
+        let payload = self.builder.build_pointer_cast(buffer, self.payload_type(&variant).ptr_type(AddressSpace::Generic), "payload");
+
+        (payload, variant)
+    }
+
+    /// Lays out the struct type of every union: its tag, then a buffer of bytes as big as its largest payload,
+    /// sized by the data layout of the module's target. the buffer comes after an empty array of the most
+    /// aligned payload, so that it is aligned for all of them. a union that holds another union is laid out
+    /// after it, as its size isn't known until then
+    pub fn define_unions(&self) {
+        let target_data = TargetData::create(self.module.get_data_layout().as_str().to_str().unwrap());
+        let mut undefined: Vec<Union> = self.unions.borrow().values().cloned().collect();
+
+        loop {
+            let before = undefined.len();
+            undefined.retain(|union| {
+                let payload_types: Vec<StructType> = union.variants.iter().map(|variant| self.payload_type(variant)).collect();
+                if !payload_types.iter().all(|payload_type| payload_type.is_sized()) {
+                    return true;
+                }
+
+                let size = payload_types.iter().map(|payload_type| target_data.get_abi_size(payload_type)).max().unwrap_or(0);
+                let most_aligned = payload_types
+                    .iter()
+                    .max_by_key(|payload_type| target_data.get_abi_alignment(*payload_type))
+                    .copied()
+                    .unwrap_or_else(|| self.context.struct_type(&[], false));
+
+                let field_types: [BasicTypeEnum; 3] = [
+                    self.tag_type().into(),
+                    most_aligned.array_type(0).into(),
+                    self.context.i8_type().array_type(size as u32).into(),
+                ];
+                self.module.get_struct_type(&union.name).unwrap().set_body(&field_types, false);
+                false
+            });
+
+            // the type checker has made sure no union holds itself, so every round lays out at least one
+            if undefined.is_empty() || undefined.len() == before {
+                break;
+            }
+        }
+    }
+
+    /// The methods in a class's vtable, in order. the slots of its base class come first, so a pointer
+    /// to the vtable can be used as one to the base class's vtable. each slot is given as the name of
+    /// the method, the class that added the slot, and the class whose version of the method is in it
+    fn vtable_slots(&self, class_name: &str) -> Vec<(String, Type, Type)> {
+        let class = self.classes.borrow()[class_name].clone();
+        let class_type = Type::Named(class.name.clone());
+
+        let mut slots = match &class.base {
+            Some(base) => self.vtable_slots(base),
+            None => vec![(DESTROY_SLOT.to_string(), class_type.clone(), class_type.clone())],
+        };
+
+        // every class destroys its own instances
+        slots[0].2 = class_type.clone();
+
+        for method in class.methods.iter() {
+            match slots.iter_mut().find(|(name, _, _)| name == method) {
+                Some(slot) => slot.2 = class_type.clone(),
+                None => slots.push((method.clone(), class_type.clone(), class_type.clone())),
+            }
+        }
+
+        slots
+    }
+
+    /// Adds the vtable of a class that has one to the module, as a constant struct of pointers to its methods.
+    /// a slot has the type of the method that added it, and overrides are cast to that type.
+    /// every method has to be declared first
+    pub fn define_vtable(&self, class: &Class) {
+        if !has_vtable(&self.classes.borrow(), &class.name) {
+            return;
+        }
+
+        let ancestry: Vec<String> = class_ancestry(&self.classes.borrow(), &class.name)
+            .iter()
+            .map(|class| class.name.clone())
+            .collect();
+        for class_name in ancestry.iter() {
+            self.destroy_function(class_name);
+        }
+
+        let (slot_types, methods): (Vec<BasicTypeEnum>, Vec<BasicValueEnum>) = self.vtable_slots(&class.name)
+            .iter()
+            .map(|(name, added_by, overridden_by)| {
+                let slot_type = self.module.get_function(&method_symbol(added_by, name)).unwrap().get_type().ptr_type(AddressSpace::Generic);
+                let method = self.module.get_function(&method_symbol(overridden_by, name)).unwrap();
+                let method = method.as_global_value().as_pointer_value().const_cast(slot_type);
+
+                (BasicTypeEnum::from(slot_type), BasicValueEnum::from(method))
+            })
+            .unzip();
+
+        let vtable_type = self.context.opaque_struct_type(&vtable_symbol(&class.name));
+        vtable_type.set_body(slot_types.as_slice(), false);
+
+        let vtable = self.module.add_global(vtable_type, None, &vtable_symbol(&class.name));
+        vtable.set_initializer(&vtable_type.const_named_struct(methods.as_slice()));
+        vtable.set_constant(true);
+        vtable.set_linkage(Linkage::Private);
+    }
+
+    /// The function in the vtable of a class that destroys an instance of it, so that deleting an object
+    /// through a pointer to its base class destroys all of it. it is added to the module the first time it is needed
+    fn destroy_function(&self, class_name: &str) -> FunctionValue<'ctx> {
+        let class_type = Type::Named(class_name.to_string());
+        let symbol = method_symbol(&class_type, DESTROY_SLOT);
+
+        if let Some(function) = self.module.get_function(&symbol) {
+            return function;
+        }
+
+        let this_type = self.to_type(&class_type).ptr_type(AddressSpace::Generic);
+        let function = self.module.add_function(&symbol, self.context.void_type().fn_type(&[this_type.into()], false), Some(Linkage::Private));
+
+        let entry_point = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry_point);
+        self.compile_destroy(function.get_nth_param(0).unwrap().into_pointer_value(), &class_type);
+        self.builder.build_return(None);
+
+        function
+    }
+
+    /// Loads a method from the vtable of an object, given the class that owns the method,
+    /// for calling the version of it that belongs to the object's actual class.
+    fn compile_vtable_lookup(&self, this: PointerValue<'ctx>, owner: &Type, name: &str, function_type: FunctionType<'ctx>) -> CallableValue<'ctx> {
+        let slot = self.vtable_slots(&owner.to_string()).iter().position(|(slot_name, _, _)| slot_name == name).unwrap();
+        let vtable_type = self.module.get_struct_type(&vtable_symbol(&owner.to_string())).unwrap();
+
+        let vtable_ptr = self.builder.build_struct_gep(this, 0, "vtableptr").unwrap();
+        let vtable = self.builder.build_load(vtable_ptr, "vtable").into_pointer_value();
+        let vtable = self.builder.build_pointer_cast(vtable, vtable_type.ptr_type(AddressSpace::Generic), "vtable");
+
+        let method_ptr = self.builder.build_struct_gep(vtable, slot as u32, name).unwrap();
+        let method = self.builder.build_load(method_ptr, name).into_pointer_value();
+        let method = self.builder.build_pointer_cast(method, function_type.ptr_type(AddressSpace::Generic), name);
+
+        CallableValue::try_from(method).unwrap()
+    }
+
+    /// Finds a function from libc that the compiler calls itself, such as malloc,
+    /// declaring it if the program hasn't.
+    fn libc_function(&self, name: &str, function_type: FunctionType<'ctx>) -> FunctionValue<'ctx> {
+        match self.module.get_function(name) {
+            Some(function) => function,
+            None => {
+                let function = self.module.add_function(name, function_type, Some(Linkage::External));
+                function.set_call_conventions(C_CALLING_CONVENTION);
+                function
+            },
+        }
+    }
+
+    /// Puts the bytes of some text in a global of their own, followed by a null terminator
+    /// so that they can be handed to C, and returns a pointer to the first of them.
+    fn compile_c_string(&self, text: &str) -> PointerValue<'ctx> {
+        let i8_type = self.context.i8_type();
+        let bytes: Vec<IntValue> = text
+            .bytes()
+            .chain(std::iter::once(0))
+            .map(|byte| i8_type.const_int(byte as u64, false))
+            .collect();
+
+        let global = self.module.add_global(i8_type.array_type(bytes.len() as u32), None, "str");
+        global.set_initializer(&i8_type.const_array(bytes.as_slice()));
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+
+        global.as_pointer_value().const_cast(i8_type.ptr_type(AddressSpace::Generic))
+    }
+
+    /// Builds the constant value of a string literal. the null terminator after
+    /// its bytes isn't counted in its length
+    fn compile_str_literal(&self, text: &str) -> StructValue<'ctx> {
+        let ptr = self.compile_c_string(text);
+        let len = self.usize_type.const_int(text.len() as u64, false);
+        self.context.const_struct(&[ptr.into(), len.into()], false)
+    }
+
+    /// Adds the printf conversion that prints a value of the given type to a format,
+    /// along with the arguments printf takes for it.
+    fn compile_format_arg(&self, value: BasicValueEnum<'ctx>, typ: &Type, format: &mut String, printf_args: &mut Vec<BasicMetadataValueEnum<'ctx>>) {
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+
+        match typ {
+            Type::Bool => {
+                let text = self.builder.build_select(value.into_int_value(), self.compile_c_string("true"), self.compile_c_string("false"), "bool");
+                format.push_str("%s");
+                printf_args.push(text.into());
+            },
+            Type::Char => {
+                format.push_str("%c");
+                printf_args.push(self.builder.build_int_z_extend(value.into_int_value(), i32_type, "char").into());
+            },
+            // strings aren't null terminated, so are printed up to their length
+            Type::Str => {
+                let string = value.into_struct_value();
+                let bytes = self.builder.build_extract_value(string, 0, "bytes").unwrap();
+                let len = self.builder.build_extract_value(string, 1, "len").unwrap().into_int_value();
+                format.push_str("%.*s");
+                printf_args.push(self.builder.build_int_truncate_or_bit_cast(len, i32_type, "len").into());
+                printf_args.push(bytes.into());
+            },
+            Type::Pointer(_) => {
+                format.push_str("%p");
+                printf_args.push(value.into());
+            },
+            // varargs are promoted to doubles and 64 bit integers, so they can all be printed the same way
+            typ if typ.is_float() => {
+                format.push_str("%g");
+                printf_args.push(self.builder.build_float_ext(value.into_float_value(), self.context.f64_type(), "float").into());
+            },
+            typ if typ.is_signed() => {
+                format.push_str("%lld");
+                printf_args.push(self.builder.build_int_s_extend(value.into_int_value(), i64_type, "int").into());
+            },
+            _ => {
+                format.push_str("%llu");
+                printf_args.push(self.builder.build_int_z_extend(value.into_int_value(), i64_type, "uint").into());
+            },
+        }
+    }
+
+    /// Aborts the program if an index isn't less than the length of what it indexes.
+    fn compile_bounds_check(&self, index: IntValue<'ctx>, len: IntValue<'ctx>, current_function: &FunctionValue<'ctx>) {
+        let out_of_bounds_block = self.context.append_basic_block(*current_function, "outofbounds");
+        let in_bounds_block = self.context.append_basic_block(*current_function, "inbounds");
+
+        let in_bounds = self.builder.build_int_compare(IntPredicate::ULT, index, len, "inbounds");
+        self.builder.build_conditional_branch(in_bounds, in_bounds_block, out_of_bounds_block);
+
+        self.builder.position_at_end(out_of_bounds_block);
+        let abort = self.libc_function("abort", self.context.void_type().fn_type(&[], false));
+        self.builder.build_call(abort, &[], "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(in_bounds_block);
+    }
+
+    /// Adds a global to the module for each static field of a class, holding its initial value.
+    pub fn define_static_fields(&self, class: &Class) {
+        let class_type = Type::Named(class.name.clone());
+
+        for field in class.fields.iter().filter(|field| field.is_static) {
+            let field_type = self.to_type(&field.typ);
+
+            let global = self.module.add_global(field_type, None, &static_field_symbol(&class_type, &field.name));
+            match &field.initializer {
+                Some(initializer) => global.set_initializer(&self.compile_literal(initializer, field_type)),
+                None => global.set_initializer(&field_type.const_zero()),
+            }
+            global.set_linkage(Linkage::Private);
+        }
+    }
+
+    /// Builds the constant value of a literal, which the type checker has given a type.
+    /// a negated literal is also allowed, as in -5, and each negation of it flips its sign again, as in - -5
+    fn compile_literal(&self, expr: &Spanned<Expr>, typ: BasicTypeEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        let mut literal = expr;
+        let mut negated = false;
+        while let Expr::Unary(UnaryOp::Neg, a) = &literal.0 {
+            literal = a;
+            negated = !negated;
+        }
+
+        let value = match &literal.0 {
+            Expr::Value(value) => value,
+            _ => unreachable!("the type checker only allows literals here"),
+        };
+
+        match (value, typ) {
+            (Value::Bool(x), BasicTypeEnum::IntType(int_type)) => int_type.const_int(*x as u64, false).into(),
+            (Value::Int(n, _), BasicTypeEnum::IntType(int_type)) => {
+                let n = if negated { n.wrapping_neg() } else { *n };
+                int_type.const_int(n, false).into()
+            },
+            (Value::Int(n, _), BasicTypeEnum::FloatType(float_type)) => {
+                let x = *n as f64;
+                float_type.const_float(if negated { -x } else { x }).into()
+            },
+            (Value::Float(x, _), BasicTypeEnum::FloatType(float_type)) => {
+                float_type.const_float(if negated { -x } else { *x }).into()
+            },
+            _ => unreachable!("the type checker only allows literals of the field's type"),
+        }
+    }
+
+    /// The index in a class's struct of the first field the class declares itself,
+    /// which comes after its vtable pointer and its inherited fields.
+    fn own_field_index(&self, class: &Class) -> usize {
+        let classes = self.classes.borrow();
+        first_field_index(&classes, &class.name) + all_fields(&classes, &class.name).len() - class.instance_fields().count()
+    }
+
+    /// Casts a pointer to an instance of a class into a pointer to its base class part, which is at the same address.
+    fn base_pointer(&self, ptr: PointerValue<'ctx>, base: &str) -> (PointerValue<'ctx>, Type) {
+        let base_type = Type::Named(base.to_string());
+        let base_ptr = self.builder.build_pointer_cast(ptr, self.to_type(&base_type).ptr_type(AddressSpace::Generic), base);
+
+        (base_ptr, base_type)
+    }
+
+    /// Adds a body-less function to the module, so that it can be called
+    /// before (or without) its definition being compiled.
+    pub fn declare_function(&self, name: &str, signature: &FunctionSignature) -> FunctionValue<'ctx> {
+        let func_type = self.to_fn_type(signature);
+
+        //TODO: set up linkage
+        self.module.add_function(name, func_type, Some(Linkage::External))
+    }
+
+    /// Declares a function that lives outside of this module, such as one from libc.
+    /// Calls to it go through the same path as calls to any other function.
+    pub fn declare_extern(&self, name: &str, signature: &FunctionSignature) -> FunctionValue<'ctx> {
+        let function = self.declare_function(name, signature);
+        function.set_call_conventions(C_CALLING_CONVENTION);
+
+        function
+    }
+
+    pub fn compile_function(
+        &self,
+        name: &str,
+        func: &FunctionDefinition,
+        func_map: &HashMap<String, FunctionSignature>,
+    ) -> Result<(FunctionType<'ctx>, FunctionValue<'ctx>), Error> {
+
+        let function = match self.module.get_function(name) {
+            Some(function) => function,
+            None => self.declare_function(name, &func.signature),
+        };
+
+        let entry_point = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry_point);
+
+        let mut variables: HashMap<String, PointerValue<'ctx>> = HashMap::new();
+        self.compile_params(function, 0, &func.signature.params, &mut variables);
+        self.compile_body(function, &func.signature.return_type, &func.body, func_map, variables)?;
+
+        Ok((function.get_type(), function))
+    }
+
+    /// Copies the parameters of a function, from the given one on, into allocas, so the body can treat them like any other variable.
+    fn compile_params(&self, function: FunctionValue<'ctx>, first: usize, params: &[(String, Type)], variables: &mut HashMap<String, PointerValue<'ctx>>) {
+        for (param, (param_name, param_type)) in function.get_param_iter().skip(first).zip(params.iter()) {
+            let alloca = self.create_entry_block_alloca(param_name, self.to_type(param_type), &function);
+            self.builder.build_store(alloca, param);
+            variables.insert(param_name.clone(), alloca);
+        }
+    }
+
+    /// Compiles the body of a function or closure, with the builder at the end of its entry block.
+    fn compile_body(
+        &self,
+        function: FunctionValue<'ctx>,
+        return_type: &Type,
+        body: &Spanned<Expr>,
+        func_map: &HashMap<String, FunctionSignature>,
+        mut variables: HashMap<String, PointerValue<'ctx>>,
+    ) -> Result<(), Error> {
+        *self.scope_stack.borrow_mut() = vec![Vec::new()];
+        self.temporaries.borrow_mut().clear();
+
+        // the value of the body is moved out of the function, as is a variable that it ends in
+        let (value, moved) = match return_type {
+            Type::Void => (self.compile_expression(body, func_map, &mut variables, &function)?, None),
+            _ => {
+                let value = self.moving(body, || self.compile_expression(body, func_map, &mut variables, &function))?;
+                (value, self.moved_variable(body, &variables, 0))
+            },
+        };
+
+        // falling off the end of the function returns the value of the body.
+        // a function with a return type that has no value here returns on every path,
+        // as the type checker has made sure of that
+        if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+            self.compile_temporaries_exit(0);
+            self.compile_scope_exit(0, moved);
+
+            match (return_type, value) {
+                (Type::Void, _) => {
+                    self.builder.build_return(None);
+                },
+                (_, Some(value)) => {
+                    self.builder.build_return(Some(&value));
+                },
+                (_, None) => {
+                    self.builder.build_unreachable();
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the code of a closure into a function of its own, which is given the closure's environment
+    /// before its parameters. the builder, and what the compiler keeps track of for the function being compiled,
+    /// are put back afterwards, so that the function the closure is in can carry on being compiled
+    fn compile_closure(
+        &self,
+        function: FunctionValue<'ctx>,
+        lambda: &Lambda,
+        env_type: StructType<'ctx>,
+        func_map: &HashMap<String, FunctionSignature>,
+    ) -> Result<(), Error> {
+        let outer_block = self.builder.get_insert_block().unwrap();
+        let scope_stack = self.scope_stack.take();
+        let temporaries = self.temporaries.take();
+        let loop_stack = self.loop_stack.take();
+        let assigned_places = self.assigned_places.take();
+        let moved = self.moved.take();
+
+        let entry_point = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry_point);
+
+        // a variable captured by value is its copy in the environment, and one captured by reference is where the environment points
+        let mut variables: HashMap<String, PointerValue<'ctx>> = HashMap::new();
+        let env = function.get_nth_param(0).unwrap().into_pointer_value();
+        let env = self.builder.build_pointer_cast(env, env_type.ptr_type(AddressSpace::Generic), "env");
+        for (index, capture) in lambda.captures.iter().enumerate() {
+            let captured = self.builder.build_struct_gep(env, index as u32, &capture.name).unwrap();
+            let captured = match capture.by_reference {
+                true => self.builder.build_load(captured, &capture.name).into_pointer_value(),
+                false => captured,
+            };
+            variables.insert(capture.name.clone(), captured);
+        }
+
+        self.compile_params(function, 1, &lambda.params, &mut variables);
+        let result = self.compile_body(function, &lambda.return_type, &lambda.body, func_map, variables);
+
+        *self.scope_stack.borrow_mut() = scope_stack;
+        *self.temporaries.borrow_mut() = temporaries;
+        *self.loop_stack.borrow_mut() = loop_stack;
+        *self.assigned_places.borrow_mut() = assigned_places;
+        *self.moved.borrow_mut() = moved;
+        self.builder.position_at_end(outer_block);
+
+        result
+    }
+
+
+    /// Compiles the body of a loop, with break and continue jumping to the given blocks.
+    /// the temporaries it makes are destroyed at the end of each time round the loop
+    fn compile_loop_body(
+        &self,
+        body: &Spanned<Expr>,
+        continue_block: BasicBlock<'ctx>,
+        break_block: BasicBlock<'ctx>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<(), Error> {
+        let depth = self.scope_stack.borrow().len();
+        let temporaries = self.temporaries.borrow().len();
+        self.loop_stack.borrow_mut().push((continue_block, break_block, depth, temporaries));
+        let result = self.compile_statement(body, funcs, variables, current_function);
+        self.loop_stack.borrow_mut().pop();
+
+        result.map(|_| ())
+    }
+
+    /// Finds the memory that an expression refers to, such as a variable or one of its fields.
+    /// other expressions are stored in a temporary, so that their fields can be read, unless
+    /// they made one already
+    fn compile_place( &self,
+        expr: &Spanned<Expr>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<PointerValue<'ctx>, Error> {
+        match &expr.0 {
+            Expr::Local(name) => match variables.get(name.as_str()) {
+                Some(var) => Ok(*var),
+                None => Err(Error {
+                    msg: format!("The variable named {} does not exist.", name),
+                    span: expr.1.clone(),
+                }),
+            },
+            Expr::Field(obj, field_name, index) => {
+                let obj = self.compile_place(obj, funcs, variables, current_function)?;
+                Ok(self.builder.build_struct_gep(obj, index.unwrap() as u32, field_name).unwrap())
+            },
+            Expr::Unary(UnaryOp::Deref, ptr) => {
+                Ok(self.compile_value(ptr, funcs, variables, current_function)?.into_pointer_value())
+            },
+            Expr::StaticField(owner, name) => {
+                Ok(self.module.get_global(&static_field_symbol(owner, name)).unwrap().as_pointer_value())
+            },
+            _ => {
+                let value = self.compile_value(expr, funcs, variables, current_function)?;
+                if let Some((temporary, _, span)) = self.temporaries.borrow().last() {
+                    if *span == expr.1 {
+                        return Ok(*temporary);
+                    }
+                }
+
+                let temporary = self.create_entry_block_alloca("tmp", value.get_type(), current_function);
+                self.builder.build_store(temporary, value);
+                Ok(temporary)
+            },
+        }
+    }
+
+    /// Stores the value that a variable declared without one starts with. that is zero,
+    /// except for the fields of classes, which are set to their initializers
+    fn compile_default_value( &self,
+        ptr: PointerValue<'ctx>,
+        typ: &Type,
+        funcs: &HashMap<String, FunctionSignature>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<(), Error> {
+        self.compile_initial_fields(ptr, typ, funcs, current_function)?;
+
+        // the type checker has made sure a type with constructors has a default one
+        if let Some(constructor) = self.module.get_function(&constructor_symbol(typ, 0)) {
+            self.builder.build_call(constructor, &[ptr.into()], "");
+        }
+
+        Ok(())
+    }
+
+    /// Stores the initial values of an object's fields into it, which happens before any constructor is called.
+    /// the base class part of the object is default constructed first
+    fn compile_initial_fields( &self,
+        ptr: PointerValue<'ctx>,
+        typ: &Type,
+        funcs: &HashMap<String, FunctionSignature>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<(), Error> {
+        self.builder.build_store(ptr, self.to_type(typ).const_zero());
+
+        if let Type::Named(class_name) = typ {
+            let class = self.classes.borrow()[class_name].clone();
+
+            if let Some(base) = &class.base {
+                let (base_ptr, base_type) = self.base_pointer(ptr, base);
+                self.compile_default_value(base_ptr, &base_type, funcs, current_function)?;
+            }
+
+            // set after the base class is constructed, which points the object at the base class's vtable
+            if let Some(vtable) = self.module.get_global(&vtable_symbol(class_name)) {
+                let vtable_ptr = self.builder.build_struct_gep(ptr, 0, "vtable").unwrap();
+                let vtable = vtable.as_pointer_value().const_cast(self.context.i8_type().ptr_type(AddressSpace::Generic));
+                self.builder.build_store(vtable_ptr, vtable);
+            }
+
+            let own_field_index = self.own_field_index(&class);
+            for (index, field) in class.instance_fields().enumerate() {
+                let field_ptr = self.builder.build_struct_gep(ptr, (own_field_index + index) as u32, &field.name).unwrap();
+
+                match &field.initializer {
+                    Some(initializer) => {
+                        let value = self.moving(initializer, || self.compile_value(initializer, funcs, &mut HashMap::new(), current_function))?;
+                        self.builder.build_store(field_ptr, value);
+                    },
+                    None => self.compile_default_value(field_ptr, &field.typ, funcs, current_function)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds an instance of a class in the given memory, from a construct expression. the fields are given
+    /// their initial values, then the constructor, if the class has constructors, is called on the instance
+    fn compile_construct( &self,
+        ptr: PointerValue<'ctx>,
+        construct: &Spanned<Expr>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<(), Error> {
+        let (typ, args, symbol) = match &construct.0 {
+            Expr::Construct(typ, args, symbol) => (typ, args, symbol),
+            _ => unreachable!("the type checker only allows constructing classes"),
+        };
+
+        self.compile_initial_fields(ptr, typ, funcs, current_function)?;
+
+        if let Some(symbol) = symbol {
+            let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::with_capacity(args.len() + 1);
+            arg_values.push(ptr.into());
+
+            for arg in args.iter() {
+                let arg_value = self.compile_value(arg, funcs, variables, current_function)?;
+                arg_values.push(arg_value.into());
+            }
+
+            let constructor = self.module.get_function(symbol).unwrap();
+            self.builder.build_call(constructor, arg_values.as_slice(), "");
+        }
+
+        Ok(())
+    }
+
+    /// Whether destroying a value of the given type does anything, because it,
+    /// one of its base classes or one of its fields has a destructor.
+    fn has_destructor(&self, typ: &Type) -> bool {
+        match typ {
+            Type::Named(class_name) => {
+                let classes = self.classes.borrow();
+                class_ancestry(&classes, class_name)
+                    .iter()
+                    .any(|class| self.module.get_function(&destructor_symbol(&Type::Named(class.name.clone()))).is_some())
+                    || all_fields(&classes, class_name).iter().any(|field| self.has_destructor(&field.typ))
+            },
+            _ => false,
+        }
+    }
+
+    /// Destroys an object by calling its destructor, then destroying its fields, last field first,
+    /// then destroying its base class part.
+    fn compile_destroy(&self, ptr: PointerValue<'ctx>, typ: &Type) {
+        if let Type::Named(class_name) = typ {
+            if let Some(destructor) = self.module.get_function(&destructor_symbol(typ)) {
+                self.builder.build_call(destructor, &[ptr.into()], "");
+            }
+
+            let class = self.classes.borrow()[class_name].clone();
+
+            let own_field_index = self.own_field_index(&class);
+            let fields: Vec<_> = class.instance_fields().enumerate().collect();
+            for (index, field) in fields.into_iter().rev().filter(|(_, field)| self.has_destructor(&field.typ)) {
+                let field_ptr = self.builder.build_struct_gep(ptr, (own_field_index + index) as u32, &field.name).unwrap();
+                self.compile_destroy(field_ptr, &field.typ);
+            }
+
+            if let Some(base) = &class.base {
+                let (base_ptr, base_type) = self.base_pointer(ptr, base);
+                self.compile_destroy(base_ptr, &base_type);
+            }
+        }
+    }
+
+    /// Destroys the objects of every scope from the given depth inwards, innermost scope first and
+    /// in reverse declaration order, for when control leaves those scopes. the scopes stay on the stack,
+    /// as a jump such as a return only leaves them on one path. a variable being moved out isn't destroyed
+    fn compile_scope_exit(&self, depth: usize, moved: Option<PointerValue<'ctx>>) {
+        for scope in self.scope_stack.borrow()[depth..].iter().rev() {
+            for (ptr, typ, live) in scope.iter().rev().filter(|(ptr, _, _)| Some(*ptr) != moved) {
+                self.compile_destroy_live(*ptr, typ, *live);
+            }
+        }
+    }
+
+    /// Destroys the object in a variable, unless it has been moved out of the variable.
+    fn compile_destroy_live(&self, ptr: PointerValue<'ctx>, typ: &Type, live: PointerValue<'ctx>) {
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let destroy_block = self.context.append_basic_block(function, "destroy");
+        let after_block = self.context.append_basic_block(function, "destroyed");
+
+        let is_live = self.builder.build_load(live, "live").into_int_value();
+        self.builder.build_conditional_branch(is_live, destroy_block, after_block);
+
+        self.builder.position_at_end(destroy_block);
+        self.compile_destroy(ptr, typ);
+        self.builder.build_unconditional_branch(after_block);
+
+        self.builder.position_at_end(after_block);
+    }
+
+    /// Whether the object in a variable that gets destroyed is still there. an unknown variable,
+    /// such as a parameter, isn't destroyed where it is used
+    fn live_flag(&self, var: PointerValue<'ctx>) -> Option<PointerValue<'ctx>> {
+        self.scope_stack.borrow().iter().flatten().find(|(ptr, _, _)| *ptr == var).map(|(_, _, live)| *live)
+    }
+
+    /// Destroys the temporaries made since there were the given number of them, last made first.
+    /// like scopes, they stay on the stack
+    fn compile_temporaries_exit(&self, start: usize) {
+        for (ptr, typ, _) in self.temporaries.borrow()[start..].iter().rev() {
+            self.compile_destroy(*ptr, typ);
+        }
+    }
+
+    /// The variable that an expression results in, if it is one of those that the scopes from the given depth inwards
+    /// destroy. it is moved out of them when they are left with its value
+    fn moved_variable(&self, expr: &Spanned<Expr>, variables: &HashMap<String, PointerValue<'ctx>>, depth: usize) -> Option<PointerValue<'ctx>> {
+        match &result_of(expr).0 {
+            Expr::Local(name) => variables
+                .get(name)
+                .copied()
+                .filter(|var| self.scope_stack.borrow()[depth..].iter().flatten().any(|(ptr, _, _)| ptr == var)),
+            _ => None,
+        }
+    }
+
+    /// The type that values of an LLVM type are destroyed as, if they need destroying,
+    /// which they do if they are objects with destructors.
+    fn destroyed_type(&self, value_type: BasicTypeEnum<'ctx>) -> Option<Type> {
+        let struct_type = match value_type {
+            BasicTypeEnum::StructType(struct_type) => struct_type,
+            _ => return None,
+        };
+
+        let typ = Type::Named(struct_type.get_name()?.to_str().unwrap().to_string());
+        let is_class = self.classes.borrow().contains_key(&typ.to_string());
+        Some(typ).filter(|typ| is_class && self.has_destructor(typ))
+    }
+
+    /// Keeps an object that an expression made as a temporary, unless the object is being moved.
+    /// values that don't need destroying aren't kept
+    fn compile_temporary(&self, value: BasicValueEnum<'ctx>, span: &Span, current_function: &FunctionValue<'ctx>) {
+        let typ = match self.destroyed_type(value.get_type()) {
+            Some(typ) if !self.moved.borrow().contains(span) => typ,
+            _ => return,
+        };
+
+        let temporary = self.create_entry_block_alloca("temporary", value.get_type(), current_function);
+        self.builder.build_store(temporary, value);
+        self.temporaries.borrow_mut().push((temporary, typ, span.clone()));
+    }
+
+    /// Compiles an expression with the object it results in being moved, rather than being a temporary.
+    fn moving<T>(&self, expr: &Spanned<Expr>, compile: impl FnOnce() -> T) -> T {
+        self.moved.borrow_mut().push(result_of(expr).1.clone());
+        let result = compile();
+        self.moved.borrow_mut().pop();
+
+        result
+    }
+
+    /// Tests whether the value in memory matches a pattern, going on to the given block if it doesn't.
+    /// the builder is left in the block that runs if it does
+    fn compile_pattern_test(&self, ptr: PointerValue<'ctx>, typ: &Type, pattern: &Spanned<Pattern>, unmatched_block: BasicBlock<'ctx>, current_function: &FunctionValue<'ctx>) {
+        let (tested, expected) = match &pattern.0 {
+            Pattern::Wildcard | Pattern::Binding(_) => return,
+            Pattern::Literal(literal) => {
+                let value = self.builder.build_load(ptr, "tested").into_int_value();
+                (value, self.compile_literal(literal, value.get_type().into()).into_int_value())
+            },
+            Pattern::Variant(_, _, tag) => {
+                let tag_ptr = self.builder.build_struct_gep(ptr, 0, "tag").unwrap();
+                let held = self.builder.build_load(tag_ptr, "tag").into_int_value();
+                (held, self.tag_type().const_int(tag.unwrap() as u64, false))
+            },
+        };
+
+        let matched_block = self.context.append_basic_block(*current_function, "matched");
+        let is_match = self.builder.build_int_compare(IntPredicate::EQ, tested, expected, "ismatch");
+        self.builder.build_conditional_branch(is_match, matched_block, unmatched_block);
+        self.builder.position_at_end(matched_block);
+
+        // a variant only matches if the values it holds do too
+        if let Pattern::Variant(_, fields, tag) = &pattern.0 {
+            let (payload, variant) = self.payload_pointer(ptr, typ, tag.unwrap());
+            for (index, (field, field_type)) in fields.iter().zip(variant.payload.iter()).enumerate() {
+                let field_ptr = self.builder.build_struct_gep(payload, index as u32, "held").unwrap();
+                self.compile_pattern_test(field_ptr, field_type, field, unmatched_block, current_function);
+            }
+        }
     }
 
-    pub fn to_type(&self, typename: &str) -> BasicTypeEnum<'ctx> {
-        match typename {
-            "f32" => self.context.f32_type().into(),
-            "f64" => self.context.f64_type().into(),
-            "i32" => self.context.i32_type().into(),
-            "i64" => self.context.i64_type().into(),
-            &_ => panic!("unknown type {}", typename)
+    /// Makes the names in a pattern that matched refer to the parts of the value in memory that they matched.
+    fn compile_bindings(&self, ptr: PointerValue<'ctx>, typ: &Type, pattern: &Spanned<Pattern>, variables: &mut HashMap<String, PointerValue<'ctx>>) {
+        match &pattern.0 {
+            Pattern::Binding(name) => {
+                variables.insert(name.clone(), ptr);
+            },
+            Pattern::Variant(_, fields, tag) => {
+                let (payload, variant) = self.payload_pointer(ptr, typ, tag.unwrap());
+                for (index, (field, field_type)) in fields.iter().zip(variant.payload.iter()).enumerate() {
+                    let field_ptr = self.builder.build_struct_gep(payload, index as u32, "held").unwrap();
+                    self.compile_bindings(field_ptr, field_type, field, variables);
+                }
+            },
+            Pattern::Wildcard | Pattern::Literal(_) => {},
         }
     }
 
-    pub fn compile_function(
-        &self,
-        name: &String,
-        func: &FunctionDefinition,
-        func_map: &HashMap<String, FunctionSignature>,
-        variables: &mut HashMap<String, PointerValue<'ctx>>
-    ) -> Result<(FunctionType, FunctionValue<'ctx>), Error> {
+    /// Compiles an expression whose temporaries are destroyed once it has run, such as a statement.
+    fn compile_statement( &self,
+        expr: &Spanned<Expr>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<Option<BasicValueEnum<'ctx>>, Error> {
+        let start = self.temporaries.borrow().len();
+        let value = self.compile_expression(expr, funcs, variables, current_function)?;
 
-        let param_types: Vec<BasicMetadataTypeEnum> = func.signature.params
-            .iter()
-            .map(|(_name, param_type)| Into::<BasicMetadataTypeEnum>::into(self.to_type(param_type)))
-            .collect::<Vec<BasicMetadataTypeEnum>>();
+        self.compile_temporaries_exit(start);
+        self.temporaries.borrow_mut().truncate(start);
 
-        let return_type = self.to_type(&func.signature.return_type);
-        let func_type = return_type.fn_type(param_types.as_slice(), false);
+        Ok(value)
+    }
 
-        //TODO: set up linkage
-        let function = self.module.add_function(name, func_type, Some(Linkage::External));
+    /// Compiles a branch, such as one of an if, which only runs some of the time. so that every temporary
+    /// is destroyed on the path that made it, the branch destroys its own, and its value is moved out of it
+    fn compile_branch( &self,
+        expr: &Spanned<Expr>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<Option<BasicValueEnum<'ctx>>, Error> {
+        self.moving(expr, || self.compile_statement(expr, funcs, variables, current_function))
+    }
 
-        let entry_point = self.context.append_basic_block(function, "entry");
-        self.builder.position_at_end(entry_point);
-        let compilation_result = self.compile_expression(&func.body, &func_map, variables, &function);
-        match compilation_result {
-            Ok(_) => (),
-            Err(err) => return Err(err),
+    /// Compiles an expression that must produce a value, such as an operand or an initializer.
+    pub fn compile_value( &self,
+        expr: &Spanned<Expr>,
+        funcs: &HashMap<String, FunctionSignature>,
+        variables: &mut HashMap<String, PointerValue<'ctx>>,
+        current_function: &FunctionValue<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, Error> {
+        match self.compile_expression(expr, funcs, variables, current_function)? {
+            Some(value) => Ok(value),
+            None => Err(Error {
+                span: expr.1.clone(),
+                msg: "expression does not produce a value".to_string(),
+            }),
         }
-
-        Ok((func_type, function))
     }
 
-
+    /// Compiles an expression. Expressions of type void, such as calls to
+    /// void functions, produce `None`.
     pub fn compile_expression( &self,
         expr: &Spanned<Expr>,
         funcs: &HashMap<String, FunctionSignature>,
         variables: &mut HashMap<String, PointerValue<'ctx>>,
         current_function: &FunctionValue<'ctx>,
-    ) -> Result<BasicValueEnum, Error> {
+    ) -> Result<Option<BasicValueEnum<'ctx>>, Error> {
         match &expr.0 {
 
             // Error expressions only get created by parser errors, so cannot exist in a valid AST
@@ -105,12 +1086,29 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
 
             Expr::Value(val) => {
                 match val {
-                    Value::Null => todo!(),
-                    Value::Bool(_) => todo!(),
-                    Value::Num(n) => Ok(self.context.i32_type().const_int(*n as u64, false).as_basic_value_enum()),
-                    Value::Str(_) => todo!(),
+                    Value::Null => Ok(None),
+                    Value::Bool(b) => Ok(Some(self.context.bool_type().const_int(*b as u64, false).as_basic_value_enum())),
+                    // the type checker has given every literal a type
+                    Value::Int(n, typ) => match self.to_type(typ.as_ref().unwrap()) {
+                        BasicTypeEnum::IntType(int_type) => Ok(Some(int_type.const_int(*n, false).as_basic_value_enum())),
+                        BasicTypeEnum::FloatType(float_type) => Ok(Some(float_type.const_float(*n as f64).as_basic_value_enum())),
+                        _ => unreachable!(),
+                    },
+                    Value::Float(x, typ) => {
+                        let float_type = self.to_type(typ.as_ref().unwrap()).into_float_type();
+                        Ok(Some(float_type.const_float(*x).as_basic_value_enum()))
+                    },
+                    Value::Str(text) => Ok(Some(self.compile_str_literal(text).into())),
                     Value::List(_) => todo!(),
-                    Value::Func(_) => todo!(),
+                    // a function used as a value is called through its thunk, and has no environment
+                    Value::Func(name) => {
+                        let signature = &funcs[name.as_str()];
+                        let params = signature.params.iter().map(|(_, param_type)| param_type.clone()).collect();
+                        let closure_type = self.to_type(&Type::Function(params, Box::new(signature.return_type.clone()))).into_struct_type();
+
+                        let code = self.function_thunk(name, signature).as_global_value().as_pointer_value();
+                        Ok(Some(closure_type.const_named_struct(&[code.into(), self.env_type().const_null().into()]).into()))
+                    },
                 }
             }
 
@@ -126,7 +1124,16 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
             
             Expr::Local(name) => {
                 match variables.get(name.as_str()) {
-                    Some(var) => Ok(self.builder.build_load(*var, name.as_str())),
+                    Some(var) => {
+                        let value = self.builder.build_load(*var, name.as_str());
+
+                        // a variable whose object is moved somewhere else, such as into another variable, no longer destroys it
+                        if let Some(live) = self.live_flag(*var).filter(|_| self.moved.borrow().last() == Some(&expr.1)) {
+                            self.builder.build_store(live, self.context.bool_type().const_zero());
+                        }
+
+                        Ok(Some(value))
+                    },
                     None => Err(Error{
                         msg: format!("The variable named {} does not exist.", name),
                         span: expr.1.clone()
@@ -140,198 +1147,852 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
                 // stack.push((local.clone(), val.clone()));
                 // val
 
-                let typ = self.to_type(typename);
+                // filled in by the type checker when the source leaves it out
+                let (typ, _) = typename.as_ref().unwrap();
 
-                let alloca = self.create_entry_block_alloca(var_name, typ, current_function);
-                let initial_val = self.compile_expression(val, funcs, variables, current_function);
-                
-                match initial_val {
-                    Ok(expr) => {
-                        self.builder.build_store(alloca, expr);
-                        variables.insert(var_name.to_string(), alloca);
+                let alloca = self.create_entry_block_alloca(var_name, self.to_type(typ), current_function);
 
-                        return initial_val;
+                match val {
+                    Some(val) => {
+                        let initial_val = self.moving(val, || self.compile_value(val, funcs, variables, current_function))?;
+                        self.builder.build_store(alloca, initial_val);
                     },
-                    Err(error) => {
-                        return Err(error);
-                    }
+                    None => self.compile_default_value(alloca, typ, funcs, current_function)?,
+                }
+
+                // the variable is destroyed when its scope ends, unless its object has been moved out of it by then
+                if self.has_destructor(typ) {
+                    let live = self.create_entry_block_alloca(&format!("{}.live", var_name), self.context.bool_type().into(), current_function);
+                    self.builder.build_store(live, self.context.bool_type().const_all_ones());
+                    self.scope_stack.borrow_mut().last_mut().unwrap().push((alloca, typ.clone(), live));
                 }
+
+                variables.insert(var_name.to_string(), alloca);
+                Ok(None)
             }
             
             Expr::Then(a, b) => {
-                self.compile_expression(a, funcs, variables, current_function)?;
+                self.compile_statement(a, funcs, variables, current_function)?;
                 self.compile_expression(b, funcs, variables, current_function)
             }
 
-            Expr::Binary(a, BinaryOp::Add, b) => {
-                let lhs = self.compile_expression(a, funcs, variables, current_function)?;
-                let rhs = self.compile_expression(b, funcs, variables, current_function)?;
+            Expr::Block(body) => {
+                // variables declared in the block go out of scope at its end
+                let mut scope = variables.clone();
+                self.scope_stack.borrow_mut().push(Vec::new());
+                let value = self.compile_expression(body, funcs, &mut scope, current_function)?;
+
+                // a variable of the block that is its value is moved out of it, rather than destroyed
+                let depth = self.scope_stack.borrow().len() - 1;
+                let moved = value.and(self.moved_variable(body, &scope, depth));
+                self.compile_scope_exit(depth, moved);
+                self.scope_stack.borrow_mut().pop();
+
+                if let (Some(_), Some(value)) = (moved, value) {
+                    self.compile_temporary(value, &result_of(body).1, current_function);
+                }
+
+                Ok(value)
+            }
+
+            Expr::Binary(a, BinaryOp::Add, b, _) => {
+                let lhs = self.compile_value(a, funcs, variables, current_function)?;
+                let rhs = self.compile_value(b, funcs, variables, current_function)?;
                 
                 match (lhs, rhs) {
                     (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
-                        => Ok(BasicValueEnum::IntValue(self.builder.build_int_add(
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_add(
                             lhs.into_int_value(),
                             rhs.into_int_value(), "intadd")
-                        )),
+                        ))),
 
-                    (BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_)) |
-                    (BasicValueEnum::FloatValue(_), BasicValueEnum::IntValue(_)) |
                     (BasicValueEnum::FloatValue(_), BasicValueEnum::FloatValue(_))
-                        => Ok(BasicValueEnum::FloatValue(self.builder.build_float_add(
+                        => Ok(Some(BasicValueEnum::FloatValue(self.builder.build_float_add(
                             lhs.into_float_value(),
                             rhs.into_float_value(), "fltadd")
-                        )),
+                        ))),
 
-                    _ => panic!("binary addition: unknown type")
+                    _ => unreachable!("binary addition: operands are checked to be the same numeric type")
                 }
             }
 
-            Expr::Binary(a, BinaryOp::Sub, b) => {
-                let lhs = self.compile_expression(a, funcs, variables, current_function)?;
-                let rhs = self.compile_expression(b, funcs, variables, current_function)?;
+            Expr::Binary(a, BinaryOp::Sub, b, _) => {
+                let lhs = self.compile_value(a, funcs, variables, current_function)?;
+                let rhs = self.compile_value(b, funcs, variables, current_function)?;
                 
                 match (lhs, rhs) {
                     (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
-                        => Ok(BasicValueEnum::IntValue(self.builder.build_int_sub(
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_sub(
                             lhs.into_int_value(),
                             rhs.into_int_value(), "intsub")
-                        )),
+                        ))),
 
-                    (BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_)) |
-                    (BasicValueEnum::FloatValue(_), BasicValueEnum::IntValue(_)) |
                     (BasicValueEnum::FloatValue(_), BasicValueEnum::FloatValue(_))
-                        => Ok(BasicValueEnum::FloatValue(self.builder.build_float_sub(
+                        => Ok(Some(BasicValueEnum::FloatValue(self.builder.build_float_sub(
                             lhs.into_float_value(),
                             rhs.into_float_value(), "fltsub")
-                        )),
+                        ))),
 
-                    _ => panic!("binary subtraction: unknown type")
+                    _ => unreachable!("binary subtraction: operands are checked to be the same numeric type")
                 }
             }
 
-            Expr::Binary(a, BinaryOp::Mul, b) => {
-                let lhs = self.compile_expression(a, funcs, variables, current_function)?;
-                let rhs = self.compile_expression(b, funcs, variables, current_function)?;
+            Expr::Binary(a, BinaryOp::Mul, b, _) => {
+                let lhs = self.compile_value(a, funcs, variables, current_function)?;
+                let rhs = self.compile_value(b, funcs, variables, current_function)?;
                 
                 match (lhs, rhs) {
                     (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
-                        => Ok(BasicValueEnum::IntValue(self.builder.build_int_mul(
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_mul(
                             lhs.into_int_value(),
                             rhs.into_int_value(), "intmul")
-                        )),
+                        ))),
 
-                    (BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_)) |
-                    (BasicValueEnum::FloatValue(_), BasicValueEnum::IntValue(_)) |
                     (BasicValueEnum::FloatValue(_), BasicValueEnum::FloatValue(_))
-                        => Ok(BasicValueEnum::FloatValue(self.builder.build_float_mul(
+                        => Ok(Some(BasicValueEnum::FloatValue(self.builder.build_float_mul(
                             lhs.into_float_value(),
                             rhs.into_float_value(), "fltmul")
-                        )),
+                        ))),
 
-                    _ => {
-                        panic!("fltmul: unknown type combination!");
-                    }
+                    _ => unreachable!("binary multiplication: operands are checked to be the same numeric type")
                 }
             }
 
-            Expr::Binary(a, BinaryOp::Div, b) => {
-                let lhs = self.compile_expression(a, funcs, variables, current_function)?;
-                let rhs = self.compile_expression(b, funcs, variables, current_function)?;
+            Expr::Binary(a, BinaryOp::Div, b, operand_type) => {
+                let lhs = self.compile_value(a, funcs, variables, current_function)?;
+                let rhs = self.compile_value(b, funcs, variables, current_function)?;
                 
                 //println!("{} + {}", a_, b_);
                 match (lhs, rhs) {
-                    (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
-                        => Ok(BasicValueEnum::IntValue(self.builder.build_int_signed_div(
+                    (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_)) if operand_type.as_ref().unwrap().is_signed()
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_signed_div(
                             lhs.into_int_value(),
                             rhs.into_int_value(), "intdiv")
-                        )),
+                        ))),
+
+                    (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_unsigned_div(
+                            lhs.into_int_value(),
+                            rhs.into_int_value(), "uintdiv")
+                        ))),
 
-                    (BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_)) |
-                    (BasicValueEnum::FloatValue(_), BasicValueEnum::IntValue(_)) |
                     (BasicValueEnum::FloatValue(_), BasicValueEnum::FloatValue(_))
-                        => Ok(BasicValueEnum::FloatValue(self.builder.build_float_div(
+                        => Ok(Some(BasicValueEnum::FloatValue(self.builder.build_float_div(
                             lhs.into_float_value(),
                             rhs.into_float_value(), "fltdiv")
-                        )),
+                        ))),
 
-                    _ => {
-                        panic!("fltdiv: unknown type combination!");
-                    }
+                    _ => unreachable!("binary division: operands are checked to be the same numeric type")
+                }
+            }
+
+            Expr::Binary(a, op @ (BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::LtEq | BinaryOp::GtEq), b, operand_type) => {
+                let lhs = self.compile_value(a, funcs, variables, current_function)?;
+                let rhs = self.compile_value(b, funcs, variables, current_function)?;
+
+                // float comparisons are ordered, except for != which is true if either side is NaN
+                let signed = operand_type.as_ref().unwrap().is_signed();
+                let (int_predicate, float_predicate) = match op {
+                    BinaryOp::Eq => (IntPredicate::EQ, FloatPredicate::OEQ),
+                    BinaryOp::NotEq => (IntPredicate::NE, FloatPredicate::UNE),
+                    BinaryOp::Lt => (if signed { IntPredicate::SLT } else { IntPredicate::ULT }, FloatPredicate::OLT),
+                    BinaryOp::Gt => (if signed { IntPredicate::SGT } else { IntPredicate::UGT }, FloatPredicate::OGT),
+                    BinaryOp::LtEq => (if signed { IntPredicate::SLE } else { IntPredicate::ULE }, FloatPredicate::OLE),
+                    BinaryOp::GtEq => (if signed { IntPredicate::SGE } else { IntPredicate::UGE }, FloatPredicate::OGE),
+                    _ => unreachable!(),
+                };
+
+                match (lhs, rhs) {
+                    (BasicValueEnum::IntValue(_), BasicValueEnum::IntValue(_))
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_compare(
+                            int_predicate,
+                            lhs.into_int_value(),
+                            rhs.into_int_value(), "intcmp")
+                        ))),
+
+                    (BasicValueEnum::FloatValue(_), BasicValueEnum::FloatValue(_))
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_float_compare(
+                            float_predicate,
+                            lhs.into_float_value(),
+                            rhs.into_float_value(), "fltcmp")
+                        ))),
+
+                    // pointers are compared by the addresses they hold
+                    (BasicValueEnum::PointerValue(_), BasicValueEnum::PointerValue(_))
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_compare(
+                            int_predicate,
+                            self.builder.build_ptr_to_int(lhs.into_pointer_value(), self.usize_type, "lhsaddr"),
+                            self.builder.build_ptr_to_int(rhs.into_pointer_value(), self.usize_type, "rhsaddr"), "ptrcmp")
+                        ))),
+
+                    _ => unreachable!("comparison: operands are checked to be the same type")
+                }
+            }
+
+            Expr::Binary(a, op @ (BinaryOp::And | BinaryOp::Or), b, _) => {
+                // the right hand side is only evaluated if the left hand side doesn't decide the result
+                let lhs = self.compile_value(a, funcs, variables, current_function)?.into_int_value();
+                let lhs_end = self.builder.get_insert_block().unwrap();
+
+                let rhs_block = self.context.append_basic_block(*current_function, "logicrhs");
+                let end_block = self.context.append_basic_block(*current_function, "logicend");
+
+                match op {
+                    BinaryOp::And => self.builder.build_conditional_branch(lhs, rhs_block, end_block),
+                    _ => self.builder.build_conditional_branch(lhs, end_block, rhs_block),
+                };
+
+                self.builder.position_at_end(rhs_block);
+                let rhs = self.compile_statement(b, funcs, variables, current_function)?.unwrap().into_int_value();
+                let rhs_end = self.builder.get_insert_block().unwrap();
+                self.builder.build_unconditional_branch(end_block);
+
+                // skipping the right hand side means false for and, and true for or
+                self.builder.position_at_end(end_block);
+                let short_circuit = self.context.bool_type().const_int((*op == BinaryOp::Or) as u64, false);
+
+                let phi = self.builder.build_phi(self.context.bool_type(), "logic");
+                phi.add_incoming(&[(&short_circuit, lhs_end), (&rhs, rhs_end)]);
+
+                Ok(Some(phi.as_basic_value()))
+            }
+
+            Expr::Unary(UnaryOp::Not, a) => {
+                let operand = self.compile_value(a, funcs, variables, current_function)?.into_int_value();
+                Ok(Some(BasicValueEnum::IntValue(self.builder.build_not(operand, "not"))))
+            }
+
+            Expr::Unary(UnaryOp::Neg, a) => {
+                let operand = self.compile_value(a, funcs, variables, current_function)?;
+
+                match operand {
+                    BasicValueEnum::IntValue(operand)
+                        => Ok(Some(BasicValueEnum::IntValue(self.builder.build_int_neg(operand, "intneg")))),
+
+                    BasicValueEnum::FloatValue(operand)
+                        => Ok(Some(BasicValueEnum::FloatValue(self.builder.build_float_neg(operand, "fltneg")))),
+
+                    _ => unreachable!("negation: the operand is checked to be numeric")
+                }
+            }
+
+            // casting a value to its own type leaves it as it is, whatever the type
+            Expr::Cast(val, typ, Some(from)) if from == typ => {
+                self.compile_expression(val, funcs, variables, current_function)
+            }
+
+            Expr::Unary(UnaryOp::Ref, a) => {
+                let place = self.compile_place(a, funcs, variables, current_function)?;
+                Ok(Some(place.as_basic_value_enum()))
+            }
+
+            Expr::Unary(UnaryOp::Deref, _a) => {
+                let place = self.compile_place(expr, funcs, variables, current_function)?;
+                Ok(Some(self.builder.build_load(place, "deref")))
+            }
+
+            // null takes on the type of the pointer it is used as
+            Expr::Cast(_, typ, Some(Type::Void)) => {
+                Ok(Some(self.to_type(typ).into_pointer_type().const_null().into()))
+            }
+
+            Expr::Cast(val, typ, from) => {
+                let value = self.compile_value(val, funcs, variables, current_function)?;
+                let signed = from.as_ref().unwrap().is_signed();
+
+                let cast = match (value, self.to_type(typ)) {
+                    (BasicValueEnum::IntValue(value), BasicTypeEnum::IntType(int_type)) => {
+                        let from_width = value.get_type().get_bit_width();
+                        let to_width = int_type.get_bit_width();
+
+                        if from_width < to_width && signed {
+                            self.builder.build_int_s_extend(value, int_type, "sext").as_basic_value_enum()
+                        } else if from_width < to_width {
+                            self.builder.build_int_z_extend(value, int_type, "zext").as_basic_value_enum()
+                        } else if from_width > to_width {
+                            self.builder.build_int_truncate(value, int_type, "trunc").as_basic_value_enum()
+                        } else {
+                            value.as_basic_value_enum()
+                        }
+                    },
+
+                    (BasicValueEnum::IntValue(value), BasicTypeEnum::FloatType(float_type)) => {
+                        if signed {
+                            self.builder.build_signed_int_to_float(value, float_type, "sitofp").as_basic_value_enum()
+                        } else {
+                            self.builder.build_unsigned_int_to_float(value, float_type, "uitofp").as_basic_value_enum()
+                        }
+                    },
+
+                    (BasicValueEnum::FloatValue(value), BasicTypeEnum::IntType(int_type)) => {
+                        if typ.is_signed() {
+                            self.builder.build_float_to_signed_int(value, int_type, "fptosi").as_basic_value_enum()
+                        } else {
+                            self.builder.build_float_to_unsigned_int(value, int_type, "fptoui").as_basic_value_enum()
+                        }
+                    },
+
+                    (BasicValueEnum::FloatValue(value), BasicTypeEnum::FloatType(float_type)) => {
+                        match (from.as_ref().unwrap(), typ) {
+                            (Type::F32, Type::F64) => self.builder.build_float_ext(value, float_type, "fpext").as_basic_value_enum(),
+                            (Type::F64, Type::F32) => self.builder.build_float_trunc(value, float_type, "fptrunc").as_basic_value_enum(),
+                            _ => value.as_basic_value_enum(),
+                        }
+                    },
+
+                    // a pointer to a class can be cast to one to its base class, which is at the same address
+                    (BasicValueEnum::PointerValue(value), BasicTypeEnum::PointerType(ptr_type)) => {
+                        self.builder.build_pointer_cast(value, ptr_type, "upcast").as_basic_value_enum()
+                    },
+
+                    _ => unreachable!("the type checker only allows casts between numbers, chars and bools, and upcasts"),
+                };
+
+                Ok(Some(cast))
+            }
+
+            Expr::Assign(target, val) => {
+                // the target is found before the value is compiled, so that a compound assignment reads it from the same place.
+                // an object being replaced is destroyed first
+                let place = self.compile_place(target, funcs, variables, current_function)?;
+
+                self.assigned_places.borrow_mut().push(place);
+                let value = self.moving(val, || self.compile_value(val, funcs, variables, current_function));
+                self.assigned_places.borrow_mut().pop();
+                let value = value?;
+
+                // a variable whose object was moved out of it has nothing to destroy, and holds an object again afterwards
+                let live = self.live_flag(place);
+                match (self.destroyed_type(value.get_type()), live) {
+                    (Some(typ), Some(live)) => self.compile_destroy_live(place, &typ, live),
+                    (Some(typ), None) => self.compile_destroy(place, &typ),
+                    (None, _) => {},
+                }
+                self.builder.build_store(place, value);
+                if let Some(live) = live {
+                    self.builder.build_store(live, self.context.bool_type().const_all_ones());
                 }
+
+                Ok(None)
+            }
+
+            Expr::AssignedValue => {
+                let place = *self.assigned_places.borrow().last().unwrap();
+                Ok(Some(self.builder.build_load(place, "current")))
+            }
+
+            Expr::Field(_, field_name, _) | Expr::StaticField(_, field_name) => {
+                let field = self.compile_place(expr, funcs, variables, current_function)?;
+                Ok(Some(self.builder.build_load(field, field_name)))
             }
 
-            Expr::Binary(a, BinaryOp::Eq, b) => {
-                unimplemented!("operator==");
+            Expr::Index(obj, index) => {
+                let string = self.compile_value(obj, funcs, variables, current_function)?.into_struct_value();
+                let index = self.compile_value(index, funcs, variables, current_function)?.into_int_value();
+
+                let bytes = self.builder.build_extract_value(string, 0, "bytes").unwrap().into_pointer_value();
+                let len = self.builder.build_extract_value(string, 1, "len").unwrap().into_int_value();
+                self.compile_bounds_check(index, len, current_function);
+
+                let byte = unsafe { self.builder.build_in_bounds_gep(bytes, &[index], "byte") };
+                Ok(Some(self.builder.build_load(byte, "char")))
             }
 
-            Expr::Binary(a, BinaryOp::NotEq, b) => {
-                //Value::Bool(self.compile(a, funcs, stack)? != self.compile(b, funcs, stack)?)
-                unimplemented!("operator!=");
+            Expr::Print(args, parts, newline) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    values.push(self.compile_value(arg, funcs, variables, current_function)?);
+                }
+
+                // the whole print is done by a single call to printf, with a format built from the parts
+                let mut format = String::new();
+                let mut printf_args: Vec<BasicMetadataValueEnum> = Vec::new();
+                for part in parts.iter() {
+                    match part {
+                        FormatPart::Text(text) => format.push_str(&text.replace('%', "%%")),
+                        FormatPart::Arg(position, typ) => self.compile_format_arg(values[*position], typ.as_ref().unwrap(), &mut format, &mut printf_args),
+                    }
+                }
+                if *newline {
+                    format.push('\n');
+                }
+                printf_args.insert(0, self.compile_c_string(&format).into());
+
+                let c_string_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+                let printf = self.libc_function("printf", self.context.i32_type().fn_type(&[c_string_type.into()], true));
+                self.builder.build_call(printf, printf_args.as_slice(), "");
+
+                Ok(None)
             }
 
             Expr::Call(func, args) => {
-                // let f = self.compile(func, funcs, stack)?;
-                // match f {
-                //     Value::Func(name) => {
-                //         let f = &funcs[&name];
-                //         let mut stack = if f.params.len() != args.len() {
-                //             return Err(Error {
-                //                 span: expr.1.clone(),
-                //                 msg: format!("'{}' called with wrong number of arguments (expected {}, found {})", name, f.params.len(), args.len()),
-                //             });
-                //         } else {
-                //             f.params
-                //                 .iter()
-                //                 .zip(args.iter())
-                //                 .map(|((name, _type), arg)| Ok((name.clone(), self.compile(arg, funcs, stack)?)))
-                //                 .collect::<Result<_, _>>()?
-                //         };
-                //         self.compile(&f.body, funcs, &mut stack)?
-                //     }
-                //     f => {
-                //         return Err(Error {
-                //             span: func.1.clone(),
-                //             msg: format!("'{:?}' is not callable", f),
-                //         })
-                //     }
-                // }
-                return Err(Error {
-                    span: expr.1.clone(),
-                    msg: format!("function call unimplemented"),
-                });
+                // the type checker has resolved the callee and checked the arguments against its signature.
+                // a callee that isn't the name of a function is a function value, whose code is given its environment first
+                let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::with_capacity(args.len() + 1);
+                let callable: CallableValue = match &func.0 {
+                    // every signature in the func_map is declared before any body is compiled
+                    Expr::Local(name) if !variables.contains_key(name) => self.module.get_function(name).unwrap().into(),
+                    _ => {
+                        let closure = self.compile_value(func, funcs, variables, current_function)?.into_struct_value();
+                        let code = self.builder.build_extract_value(closure, 0, "code").unwrap().into_pointer_value();
+                        let env = self.builder.build_extract_value(closure, 1, "env").unwrap();
+                        arg_values.push(env.into());
+                        CallableValue::try_from(code).unwrap()
+                    },
+                };
+
+                for arg in args.iter() {
+                    let arg_value = self.compile_value(arg, funcs, variables, current_function)?;
+                    arg_values.push(arg_value.into());
+                }
+
+                // calls to void functions produce no value
+                let result = self.builder.build_call(callable, arg_values.as_slice(), "call").try_as_basic_value().left();
+                if let Some(result) = result {
+                    self.compile_temporary(result, &expr.1, current_function);
+                }
+
+                Ok(result)
+            }
+
+            Expr::MethodCall(obj, name, args, owner) => {
+                // the type checker has made sure the object is a pointer to the method's owner, which is passed as 'this'
+                let this = self.compile_value(obj, funcs, variables, current_function)?.into_pointer_value();
+
+                let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::with_capacity(args.len() + 1);
+                arg_values.push(this.into());
+
+                for arg in args.iter() {
+                    let arg_value = self.compile_value(arg, funcs, variables, current_function)?;
+                    arg_values.push(arg_value.into());
+                }
+
+                let owner = owner.as_ref().unwrap();
+                let function = self.module.get_function(&method_symbol(owner, name)).unwrap();
+
+                // the object could be an instance of a derived class, so the method to call is looked up in its vtable
+                let callable: CallableValue = match self.module.get_global(&vtable_symbol(&owner.to_string())) {
+                    Some(_) => self.compile_vtable_lookup(this, owner, name, function.get_type()),
+                    None => function.into(),
+                };
+
+                let result = self.builder.build_call(callable, arg_values.as_slice(), "call").try_as_basic_value().left();
+                if let Some(result) = result {
+                    self.compile_temporary(result, &expr.1, current_function);
+                }
+
+                Ok(result)
+            }
+
+            Expr::Variant(typ, _, args, tag) => {
+                let tag = tag.unwrap();
+
+                // the union is built in memory of its own, so that its buffer can be written to as the variant's payload
+                let union = self.create_entry_block_alloca("union", self.to_type(typ), current_function);
+                let tag_ptr = self.builder.build_struct_gep(union, 0, "tag").unwrap();
+                self.builder.build_store(tag_ptr, self.tag_type().const_int(tag as u64, false));
+
+                let (payload, _) = self.payload_pointer(union, typ, tag);
+                for (index, arg) in args.iter().enumerate() {
+                    let value = self.compile_value(arg, funcs, variables, current_function)?;
+                    let value_ptr = self.builder.build_struct_gep(payload, index as u32, "value").unwrap();
+                    self.builder.build_store(value_ptr, value);
+                }
+
+                Ok(Some(self.builder.build_load(union, "variant")))
+            }
+
+            Expr::Lambda(lambda) => {
+                // the captured variables are copied into an environment in the frame of the function the closure is made in,
+                // which the type checker doesn't let the closure outlive. the ones captured by reference are kept as pointers
+                // to them. each closure has one environment per call of the function, which it fills again each time it is made
+                let field_types: Vec<BasicTypeEnum> = lambda.captures
+                    .iter()
+                    .map(|capture| match self.to_type(capture.typ.as_ref().unwrap()) {
+                        typ if capture.by_reference => typ.ptr_type(AddressSpace::Generic).into(),
+                        typ => typ,
+                    })
+                    .collect();
+                let env_type = self.context.struct_type(field_types.as_slice(), false);
+
+                let env = match lambda.captures.is_empty() {
+                    true => self.env_type().const_null(),
+                    false => {
+                        let memory = self.create_entry_block_alloca("env", env_type.into(), current_function);
+                        self.builder.build_pointer_cast(memory, self.env_type(), "memory")
+                    },
+                };
 
+                let env_ptr = self.builder.build_pointer_cast(env, env_type.ptr_type(AddressSpace::Generic), "env");
+                for (index, capture) in lambda.captures.iter().enumerate() {
+                    let var = variables[&capture.name];
+                    let captured = self.builder.build_struct_gep(env_ptr, index as u32, &capture.name).unwrap();
+                    match capture.by_reference {
+                        true => self.builder.build_store(captured, var),
+                        false => self.builder.build_store(captured, self.builder.build_load(var, &capture.name)),
+                    };
+                }
+
+                let params: Vec<Type> = lambda.params.iter().map(|(_, param_type)| param_type.clone()).collect();
+                let symbol = format!("{}.closure", current_function.get_name().to_str().unwrap());
+                let function = self.module.add_function(&symbol, self.closure_fn_type(&params, &lambda.return_type), None);
+                self.compile_closure(function, lambda, env_type, funcs)?;
+
+                let closure_type = self.to_type(&Type::Function(params, Box::new(lambda.return_type.clone()))).into_struct_type();
+                let closure = closure_type.get_undef();
+                let closure = self.builder.build_insert_value(closure, function.as_global_value().as_pointer_value(), 0, "closure").unwrap().into_struct_value();
+                let closure = self.builder.build_insert_value(closure, env, 1, "closure").unwrap().into_struct_value();
+
+                Ok(Some(closure.as_basic_value_enum()))
+            }
+
+            Expr::Is(union, _, tag) => {
+                let union = self.compile_value(union, funcs, variables, current_function)?.into_struct_value();
+                let held = self.builder.build_extract_value(union, 0, "tag").unwrap().into_int_value();
+                let tag = self.tag_type().const_int(tag.unwrap() as u64, false);
+
+                Ok(Some(self.builder.build_int_compare(IntPredicate::EQ, held, tag, "is").into()))
+            }
+
+            Expr::Construct(typ, _, _) => {
+                // the object is built in memory of its own, as the constructor is given a pointer to it
+                let construct = self.create_entry_block_alloca("construct", self.to_type(typ), current_function);
+                self.compile_construct(construct, expr, funcs, variables, current_function)?;
+
+                let constructed = self.builder.build_load(construct, "constructed");
+                self.compile_temporary(constructed, &expr.1, current_function);
+
+                Ok(Some(constructed))
+            }
+
+            Expr::New(construct) => {
+                let typ = match &construct.0 {
+                    Expr::Construct(typ, _, _) => typ.clone(),
+                    _ => unreachable!("the type checker only allows new on classes"),
+                };
+                let void_ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+                let malloc = self.libc_function("malloc", void_ptr_type.fn_type(&[self.usize_type.into()], false));
+                let size = self.builder.build_int_cast(self.to_type(&typ).size_of().unwrap(), self.usize_type, "size");
+                let memory = self.builder.build_call(malloc, &[size.into()], "memory").try_as_basic_value().left().unwrap();
+
+                let ptr = self.builder.build_pointer_cast(memory.into_pointer_value(), self.to_type(&typ).ptr_type(AddressSpace::Generic), "new");
+                self.compile_construct(ptr, construct, funcs, variables, current_function)?;
+
+                Ok(Some(ptr.into()))
+            }
+
+            Expr::Delete(ptr, pointee) => {
+                let ptr = self.compile_value(ptr, funcs, variables, current_function)?.into_pointer_value();
+                let pointee = pointee.as_ref().unwrap();
+
+                // deleting null does nothing
+                let delete_block = self.context.append_basic_block(*current_function, "delete");
+                let end_block = self.context.append_basic_block(*current_function, "deleteend");
+                let is_null = self.builder.build_is_null(ptr, "isnull");
+                self.builder.build_conditional_branch(is_null, end_block, delete_block);
+                self.builder.position_at_end(delete_block);
+
+                // an object with a vtable could be an instance of a derived class, which its vtable knows how to destroy
+                match self.module.get_global(&vtable_symbol(&pointee.to_string())) {
+                    Some(_) => {
+                        let destroy = self.compile_vtable_lookup(ptr, pointee, DESTROY_SLOT, self.destroy_function(&pointee.to_string()).get_type());
+                        self.builder.build_call(destroy, &[ptr.into()], "");
+                    },
+                    None => self.compile_destroy(ptr, pointee),
+                }
+
+                let void_ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+                let free = self.libc_function("free", self.context.void_type().fn_type(&[void_ptr_type.into()], false));
+                let memory = self.builder.build_pointer_cast(ptr, void_ptr_type, "memory");
+                self.builder.build_call(free, &[memory.into()], "");
+                self.builder.build_unconditional_branch(end_block);
+
+                self.builder.position_at_end(end_block);
+                Ok(None)
             }
 
             Expr::If(cond, a, b) => {
-                // let c = self.compile(cond, funcs, stack)?;
-                // match c {
-                //     Value::Bool(true) => self.compile(a, funcs, stack)?,
-                //     Value::Bool(false) => self.compile(b, funcs, stack)?,
-                //     c => {
-                //         return Err(Error {
-                //             span: cond.1.clone(),
-                //             msg: format!("Conditions must be booleans, found '{:?}'", c),
-                //         })
-                //     }
-                // }
-                return Err(Error {
-                    span: expr.1.clone(),
-                    msg: format!("if statement unimplemented"),
-                });
+                let cond = self.compile_value(cond, funcs, variables, current_function)?.into_int_value();
+
+                let then_block = self.context.append_basic_block(*current_function, "then");
+                let else_block = self.context.append_basic_block(*current_function, "else");
+                let merge_block = self.context.append_basic_block(*current_function, "ifcont");
+
+                self.builder.build_conditional_branch(cond, then_block, else_block);
+
+                // compiling a branch can leave the builder in a different block than it started in,
+                // so the block that actually flows into the merge block is taken afterwards
+                self.builder.position_at_end(then_block);
+                let then_val = self.compile_branch(a, funcs, variables, current_function)?;
+                self.builder.build_unconditional_branch(merge_block);
+                let then_end = self.builder.get_insert_block().unwrap();
+
+                self.builder.position_at_end(else_block);
+                let else_val = self.compile_branch(b, funcs, variables, current_function)?;
+                self.builder.build_unconditional_branch(merge_block);
+                let else_end = self.builder.get_insert_block().unwrap();
+
+                self.builder.position_at_end(merge_block);
+
+                // the branches' objects are moved into the value of the if, which is a temporary of its own
+                let value = match (then_val, else_val) {
+                    (Some(then_val), Some(else_val)) => {
+                        let phi = self.builder.build_phi(then_val.get_type(), "iftmp");
+                        phi.add_incoming(&[(&then_val, then_end), (&else_val, else_end)]);
+
+                        Some(phi.as_basic_value())
+                    },
+                    // a branch that always returns has no value, but the dead block it ends in
+                    // still flows into the merge block, so it gives the phi a placeholder
+                    (Some(then_val), None) => {
+                        let phi = self.builder.build_phi(then_val.get_type(), "iftmp");
+                        phi.add_incoming(&[(&then_val, then_end), (&then_val.get_type().const_zero(), else_end)]);
+
+                        Some(phi.as_basic_value())
+                    },
+                    (None, Some(else_val)) => {
+                        let phi = self.builder.build_phi(else_val.get_type(), "iftmp");
+                        phi.add_incoming(&[(&else_val.get_type().const_zero(), then_end), (&else_val, else_end)]);
+
+                        Some(phi.as_basic_value())
+                    },
+                    (None, None) => None,
+                };
+
+                if let Some(value) = value {
+                    self.compile_temporary(value, &expr.1, current_function);
+                }
+                Ok(value)
+            }
+
+            Expr::Match(value, arms, typ) => {
+                let typ = typ.as_ref().unwrap();
+                let value = self.compile_value(value, funcs, variables, current_function)?;
+
+                // the value is kept in memory of its own, so that the values its variants hold can be read from it
+                let matched = self.create_entry_block_alloca("matched", value.get_type(), current_function);
+                self.builder.build_store(matched, value);
+
+                let arm_blocks: Vec<BasicBlock> = arms.iter().map(|_| self.context.append_basic_block(*current_function, "arm")).collect();
+                let merge_block = self.context.append_basic_block(*current_function, "matchcont");
+
+                // the type checker has made sure that every value matches an arm, so no value gets past the tests
+                let unmatched_block = self.context.append_basic_block(*current_function, "unmatched");
+
+                if arms.iter().flat_map(|arm| arm.patterns.iter()).all(|pattern| is_flat_pattern(&pattern.0)) {
+                    // when only the literal or variant that the value starts with is tested, a switch jumps straight to the arm
+                    let tested = if self.unions.borrow().contains_key(&typ.to_string()) {
+                        self.builder.build_extract_value(value.into_struct_value(), 0, "tag").unwrap().into_int_value()
+                    } else {
+                        value.into_int_value()
+                    };
+
+                    let mut cases = Vec::new();
+                    let mut default_block = unmatched_block;
+                    for (arm, arm_block) in arms.iter().zip(arm_blocks.iter()) {
+                        for pattern in arm.patterns.iter() {
+                            match &pattern.0 {
+                                Pattern::Literal(literal) => {
+                                    cases.push((self.compile_literal(literal, tested.get_type().into()).into_int_value(), *arm_block));
+                                },
+                                Pattern::Variant(_, _, tag) => {
+                                    cases.push((self.tag_type().const_int(tag.unwrap() as u64, false), *arm_block));
+                                },
+                                // a pattern that matches anything is the last one that can be reached
+                                Pattern::Wildcard | Pattern::Binding(_) => default_block = *arm_block,
+                            }
+                        }
+                    }
+
+                    self.builder.build_switch(tested, default_block, &cases);
+                } else {
+                    // otherwise the patterns are tested in order, each going on to the next if it doesn't match
+                    for (arm, arm_block) in arms.iter().zip(arm_blocks.iter()) {
+                        for pattern in arm.patterns.iter() {
+                            let next_block = self.context.append_basic_block(*current_function, "nextpattern");
+                            self.compile_pattern_test(matched, typ, pattern, next_block, current_function);
+                            self.builder.build_unconditional_branch(*arm_block);
+                            self.builder.position_at_end(next_block);
+                        }
+                    }
+
+                    self.builder.build_unconditional_branch(unmatched_block);
+                }
+
+                self.builder.position_at_end(unmatched_block);
+                self.builder.build_unreachable();
+
+                let mut incoming = Vec::new();
+                for (arm, arm_block) in arms.iter().zip(arm_blocks.iter()) {
+                    self.builder.position_at_end(*arm_block);
+
+                    // the names in the arm's pattern refer to what they matched while the arm runs.
+                    // an arm with more than one pattern has none
+                    let mut scope = variables.clone();
+                    if let [pattern] = arm.patterns.as_slice() {
+                        self.compile_bindings(matched, typ, pattern, &mut scope);
+                    }
+
+                    let arm_value = self.compile_branch(&arm.body, funcs, &mut scope, current_function)?;
+                    self.builder.build_unconditional_branch(merge_block);
+                    incoming.push((arm_value, self.builder.get_insert_block().unwrap()));
+                }
+
+                self.builder.position_at_end(merge_block);
+
+                // the arms' objects are moved into the value of the match. as with an if,
+                // an arm that always returns has no value, so gives the phi a placeholder
+                let value = match incoming.iter().find_map(|(arm_value, _)| *arm_value) {
+                    Some(arm_value) => {
+                        let value_type = arm_value.get_type();
+                        let phi = self.builder.build_phi(value_type, "matchtmp");
+                        for (arm_value, arm_end) in incoming.iter() {
+                            let arm_value = arm_value.unwrap_or_else(|| value_type.const_zero());
+                            phi.add_incoming(&[(&arm_value, *arm_end)]);
+                        }
+
+                        Some(phi.as_basic_value())
+                    },
+                    None => None,
+                };
+
+                if let Some(value) = value {
+                    self.compile_temporary(value, &expr.1, current_function);
+                }
+                Ok(value)
+            }
+
+            Expr::While(cond, body) => {
+                let cond_block = self.context.append_basic_block(*current_function, "whilecond");
+                let body_block = self.context.append_basic_block(*current_function, "whilebody");
+                let end_block = self.context.append_basic_block(*current_function, "whileend");
+
+                self.builder.build_unconditional_branch(cond_block);
+
+                self.builder.position_at_end(cond_block);
+                let cond = self.compile_statement(cond, funcs, variables, current_function)?.unwrap().into_int_value();
+                self.builder.build_conditional_branch(cond, body_block, end_block);
+
+                self.builder.position_at_end(body_block);
+                self.compile_loop_body(body, cond_block, end_block, funcs, variables, current_function)?;
+                self.builder.build_unconditional_branch(cond_block);
+
+                self.builder.position_at_end(end_block);
+                Ok(None)
+            }
+
+            Expr::For(header, body) => {
+                // the loop variable is only in scope for the loop
+                let mut scope = variables.clone();
+                self.scope_stack.borrow_mut().push(Vec::new());
+
+                let cond_block = self.context.append_basic_block(*current_function, "forcond");
+                let body_block = self.context.append_basic_block(*current_function, "forbody");
+                let step_block = self.context.append_basic_block(*current_function, "forstep");
+                let end_block = self.context.append_basic_block(*current_function, "forend");
+
+                // set up the loop variable. the end of a range is only evaluated once
+                let range = match header {
+                    ForHeader::Standard(init, _cond, _step) => {
+                        self.compile_statement(init, funcs, &mut scope, current_function)?;
+                        None
+                    },
+                    ForHeader::Range(var_name, _typ, start, end, _step) => {
+                        let start = self.compile_value(start, funcs, &mut scope, current_function)?.into_int_value();
+                        let end = self.compile_value(end, funcs, &mut scope, current_function)?.into_int_value();
+
+                        let alloca = self.create_entry_block_alloca(var_name, start.get_type().into(), current_function);
+                        self.builder.build_store(alloca, start);
+                        scope.insert(var_name.clone(), alloca);
+
+                        Some((alloca, end))
+                    },
+                    ForHeader::Each(..) => unreachable!("the type checker rejects iterating, as there are no collections yet"),
+                };
+
+                self.builder.build_unconditional_branch(cond_block);
+
+                self.builder.position_at_end(cond_block);
+                let cond = match (header, range) {
+                    (ForHeader::Standard(_init, cond, _step), _) => {
+                        self.compile_statement(cond, funcs, &mut scope, current_function)?.unwrap().into_int_value()
+                    },
+                    (ForHeader::Range(var_name, typ, ..), Some((alloca, end))) => {
+                        let current = self.builder.build_load(alloca, var_name).into_int_value();
+                        let predicate = if typ.as_ref().unwrap().0.is_signed() { IntPredicate::SLT } else { IntPredicate::ULT };
+                        self.builder.build_int_compare(predicate, current, end, "rangecond")
+                    },
+                    _ => unreachable!(),
+                };
+                self.builder.build_conditional_branch(cond, body_block, end_block);
+
+                self.builder.position_at_end(body_block);
+                self.compile_loop_body(body, step_block, end_block, funcs, &mut scope, current_function)?;
+                self.builder.build_unconditional_branch(step_block);
+
+                // a range without an explicit step counts up by one
+                self.builder.position_at_end(step_block);
+                match (header, range) {
+                    (ForHeader::Standard(_, _, step), _) | (ForHeader::Range(_, _, _, _, Some(step)), _) => {
+                        self.compile_statement(step, funcs, &mut scope, current_function)?;
+                    },
+                    (ForHeader::Range(var_name, _, _, _, None), Some((alloca, _end))) => {
+                        let current = self.builder.build_load(alloca, var_name).into_int_value();
+                        let one = current.get_type().const_int(1, false);
+                        self.builder.build_store(alloca, self.builder.build_int_add(current, one, "rangestep"));
+                    },
+                    _ => unreachable!(),
+                }
+                self.builder.build_unconditional_branch(cond_block);
+
+                self.builder.position_at_end(end_block);
+                let depth = self.scope_stack.borrow().len() - 1;
+                self.compile_scope_exit(depth, None);
+                self.scope_stack.borrow_mut().pop();
+
+                Ok(None)
+            }
+
+            Expr::Break | Expr::Continue => {
+                let (continue_block, break_block, depth, temporaries) = *self.loop_stack.borrow().last().unwrap();
+
+                // the scopes inside the loop are left, but the loop itself isn't
+                self.compile_temporaries_exit(temporaries);
+                self.compile_scope_exit(depth, None);
+
+                match &expr.0 {
+                    Expr::Break => self.builder.build_unconditional_branch(break_block),
+                    _ => self.builder.build_unconditional_branch(continue_block),
+                };
+
+                // anything after the jump is dead, but still needs a block to go in
+                let after_jump = self.context.append_basic_block(*current_function, "afterjump");
+                self.builder.position_at_end(after_jump);
+
+                Ok(None)
             }
 
             Expr::Ret(ret_expr) => {
-                let res = self.compile_expression(ret_expr, funcs, variables, current_function);
+                let res = self.moving(ret_expr, || self.compile_value(ret_expr, funcs, variables, current_function));
                 match res {
                     Ok(ret_val) => {
+                        // every scope of the function is left, and a variable being returned is moved out of it
+                        self.compile_temporaries_exit(0);
+                        self.compile_scope_exit(0, self.moved_variable(ret_expr, variables, 0));
                         self.builder.build_return(Some(&ret_val));
+
+                        // anything after the return is dead, but still needs a block to go in
+                        let after_return = self.context.append_basic_block(*current_function, "afterret");
+                        self.builder.position_at_end(after_return);
+
+                        Ok(None)
                     },
                     Err(err) => {
                         return Err(err);
                     }
                     
                 }
-                res
             },
         }
     }
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::ast;
+use crate::ast::Error;
+use crate::ast::FunctionSignature;
+use crate::typecheck::TypeChecker;
+use crate::infer_return_types;
+use crate::map_unit;
+
+/// How many instances of generic functions a program can have. a generic function that calls itself
+/// with an ever larger type, as f<T> calling f<T*> does, would otherwise be instantiated forever
+const MAX_INSTANCES: usize = 1000;
+
+/// Names the instances of generic classes that each unit uses, and returns those instances.
+/// generic functions are left as they are, as only their own instances use concrete types
+pub fn name_instances(
+    units: &mut [ast::ProgramUnit],
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    errs: &mut Vec<Error>,
+) -> Vec<(String, Vec<ast::Type>)> {
+    let mut instances = Vec::new();
+
+    for unit in units.iter_mut() {
+        if matches!(unit, ast::ProgramUnit::Function(function) if !function.definition.signature.generic_params.is_empty()) {
+            continue;
+        }
+
+        let mut used = Vec::new();
+        unit.name_instances(class_templates, &mut used);
+
+        for (template, type_args) in used {
+            match type_arg_count_error(&template, &type_args, class_templates, unit.span()) {
+                Some(error) => errs.push(error),
+                None if class_templates.contains_key(&template) => instances.push((template, type_args)),
+                // the type checker reports the name as an unknown type
+                None => (),
+            }
+        }
+    }
+
+    instances
+}
+
+/// The error for an instance of a generic class that is given the wrong number of type arguments, if it is.
+/// such an instance is never made
+fn type_arg_count_error(
+    template: &str,
+    type_args: &[ast::Type],
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    span: &ast::Span,
+) -> Option<Error> {
+    let generic_params = &class_templates.get(template)?.class.generic_params;
+    (generic_params.len() != type_args.len()).then(|| Error {
+        span: span.clone(),
+        msg: format!(
+            "{} takes {} type arguments, but is given {} in {}",
+            template,
+            generic_params.len(),
+            type_args.len(),
+            ast::instance_symbol(template, type_args),
+        ),
+    })
+}
+
+/// Whether an instance of a generic class is yet to be made.
+pub fn is_unmade_instance(
+    template: &str,
+    type_args: &[ast::Type],
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    class_map: &HashMap<String, ast::Class>,
+) -> bool {
+    class_templates.get(template).is_some_and(|class_template| class_template.class.generic_params.len() == type_args.len())
+        && !class_map.contains_key(&ast::instance_symbol(template, type_args))
+}
+
+/// Whether a function that belongs to an instance of a generic class is only instantiated once it is called.
+/// constructors and destructors are called by the compiler as well, and classes with vtables need every method
+fn is_called_on_demand(function: &ast::NamedFunction, class_map: &HashMap<String, ast::Class>) -> bool {
+    let owner = function.definition.signature.owner.as_ref().and_then(owner_name);
+    function.name != "new" && function.name != "delete" && owner.is_some_and(|owner| !ast::has_vtable(class_map, owner))
+}
+
+/// Makes the instances of generic classes that are used, along with the functions that belong to them,
+/// and the instances that those use in turn. returns the units that make up the new instances
+fn instantiate_classes(
+    mut instances: Vec<(String, Vec<ast::Type>)>,
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    instantiated: &mut HashSet<String>,
+    errs: &mut Vec<Error>,
+) -> Vec<ast::ProgramUnit> {
+    let mut units = Vec::new();
+
+    while let Some((template, type_args)) = instances.pop() {
+        let class_template = match class_templates.get(&template) {
+            Some(class_template) if class_template.class.generic_params.len() == type_args.len() => class_template,
+            _ => continue,
+        };
+
+        if !instantiated.insert(ast::instance_symbol(&template, &type_args)) {
+            continue;
+        }
+
+        if instantiated.len() > MAX_INSTANCES {
+            errs.push(Error {
+                span: class_template.class.span.clone(),
+                msg: format!("{} has more than {} instances, so is likely instantiating itself forever", template, MAX_INSTANCES),
+            });
+            break;
+        }
+
+        let (class, functions) = class_template.instantiate(&type_args);
+        let mut new_units: Vec<ast::ProgramUnit> = std::iter::once(ast::ProgramUnit::Class(class))
+            .chain(functions.into_iter().map(ast::ProgramUnit::Function))
+            .collect();
+
+        instances.extend(name_instances(&mut new_units, class_templates, errs));
+        units.extend(new_units);
+    }
+
+    units
+}
+
+/// The name of the class a method belongs to, whether or not it is written with type arguments, as List<T> is.
+pub fn owner_name(owner: &ast::Type) -> Option<&String> {
+    match owner {
+        ast::Type::Named(name) | ast::Type::Generic(name, _) => Some(name),
+        _ => None,
+    }
+}
+
+/// Type checks the units of a program, along with the instances of generic classes and functions that they use,
+/// and returns the units that were checked and the errors found checking them. this happens in rounds, as checking can call for instances of generic
+/// functions, which are checked in the next round. a function whose calls turn out to use an instance of a
+/// generic class that doesn't exist yet is checked again once it does
+pub fn check_in_rounds(
+    units: Vec<ast::ProgramUnit>,
+    mut class_uses: Vec<(String, Vec<ast::Type>)>,
+    templates: &HashMap<String, ast::NamedFunction>,
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    func_map: &mut HashMap<String, FunctionSignature>,
+    class_map: &mut HashMap<String, ast::Class>,
+    union_map: &mut HashMap<String, ast::Union>,
+) -> (Vec<ast::ProgramUnit>, Vec<Error>) {
+    let mut pending = units;
+    let mut checked = Vec::new();
+    let mut errs = Vec::new();
+    let mut instantiated_classes: HashSet<String> = HashSet::new();
+    let mut instantiated: HashSet<String> = HashSet::new();
+    let mut uncalled_methods: HashMap<String, ast::NamedFunction> = HashMap::new();
+    let mut return_types_inferred = false;
+
+    loop {
+        //the methods of an instance are only checked and compiled once they are called, unless they are needed for its vtable
+        for unit in instantiate_classes(class_uses, class_templates, &mut instantiated_classes, &mut errs) {
+            map_unit(&unit, func_map, class_map, union_map, &mut errs);
+            match unit {
+                ast::ProgramUnit::Function(function) if is_called_on_demand(&function, class_map) => {
+                    uncalled_methods.insert(function.symbol(), function);
+                },
+                unit => pending.push(unit),
+            }
+        }
+
+        //return types are inferred once the instances that the program writes out are made, as the functions can use them
+        if !return_types_inferred {
+            infer_return_types(&mut pending, func_map, class_map, union_map, class_templates, &mut errs);
+            return_types_inferred = true;
+        }
+
+        if pending.is_empty() {
+            break;
+        }
+
+        let mut type_checker = TypeChecker::new(func_map, class_map, union_map);
+
+        let mut deferred = Vec::new();
+        for mut unit in pending.drain(..) {
+            let unchecked = unit.clone();
+            let known_instances = type_checker.class_instances.len();
+
+            let result = match &mut unit {
+                ast::ProgramUnit::Class(class) => type_checker.check_class(class),
+                ast::ProgramUnit::Union(union) => type_checker.check_union(union),
+                ast::ProgramUnit::Function(function) => type_checker.check_function(&function.name, &mut function.definition),
+                ast::ProgramUnit::Extern(function) => type_checker.check_signature(&function.signature),
+            };
+            let result = result.map_err(|error| type_checker.with_notes(error));
+
+            //an instance given the wrong number of type arguments is never made, so it is reported rather than waited for
+            let used = &type_checker.class_instances[known_instances..];
+            let miscounted = used
+                .iter()
+                .find_map(|(template, type_args)| type_arg_count_error(template, type_args, class_templates, unit.span()));
+            if let Some(error) = miscounted {
+                errs.push(error);
+                continue;
+            }
+
+            //once there are too many instances, no more are made, so waiting for one would never end
+            let missing_instance = instantiated_classes.len() <= MAX_INSTANCES
+                && used.iter().any(|(template, type_args)| is_unmade_instance(template, type_args, class_templates, class_map));
+
+            if missing_instance {
+                deferred.push(unchecked);
+                continue;
+            }
+
+            if let Err(errors) = result {
+                errs.extend(errors);
+            }
+            checked.push(unit);
+        }
+
+        class_uses = std::mem::take(&mut type_checker.class_instances);
+
+        for symbol in type_checker.called.iter() {
+            if let Some(method) = uncalled_methods.remove(symbol) {
+                pending.push(ast::ProgramUnit::Function(method));
+            }
+        }
+
+        //checking an instance can call for instances of other generic functions, or of itself for other types
+        while let Some((template, type_args)) = type_checker.instances.pop() {
+            let template = &templates[&template];
+            let instance = template.instantiate(&type_args);
+            if !instantiated.insert(instance.name.clone()) {
+                continue;
+            }
+
+            if instantiated.len() > MAX_INSTANCES {
+                errs.push(Error {
+                    span: template.definition.signature.span.clone(),
+                    msg: format!("{} has more than {} instances, so is likely instantiating itself forever", template.name, MAX_INSTANCES),
+                });
+                break;
+            }
+
+            let mut instance = [ast::ProgramUnit::Function(instance)];
+            class_uses.extend(name_instances(&mut instance, class_templates, &mut errs));
+            pending.extend(instance);
+        }
+
+        pending.extend(deferred);
+    }
+
+    (checked, errs)
+}
@@ -5,28 +5,72 @@ use crate::ast::Token;
 use crate::ast::Span;
 
 pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
-    // A parser for numbers
-    let num = text::int(10)
-        .chain::<char, _, _>(just('.').chain(text::digits(10)).or_not().flatten())
+    // A parser for numbers. they can be hex (0x), octal (0o) or binary (0b), have '_' separators,
+    // and end in a type suffix like 10i64 or 1.5f32. the parser works out what the text means
+    let radix_int = just('0')
+        .chain(one_of("xob"))
+        .chain::<char, _, _>(filter(|c: &char| c.is_ascii_hexdigit() || *c == '_').repeated().at_least(1));
+    let digits = filter(|c: &char| c.is_ascii_digit())
+        .chain::<char, _, _>(filter(|c: &char| c.is_ascii_digit() || *c == '_').repeated());
+    let decimal = digits
+        .chain::<char, _, _>(just('.').chain(digits).or_not().flatten());
+    let num = radix_int
+        .or(decimal)
+        .chain::<char, _, _>(text::ident().or_not())
         .collect::<String>()
         .map(Token::Num);
 
+    // An escape sequence in a string, such as \n, \" or \u{1F600}
+    let unicode_escape = just('u')
+        .ignore_then(filter(|c: &char| c.is_ascii_hexdigit()).repeated().at_least(1).delimited_by(just('{'), just('}')))
+        .collect::<String>()
+        .try_map(|digits, span| u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| Simple::custom(span, format!("\\u{{{}}} is not a unicode character", digits))));
+    let escape = just('\\').ignore_then(
+        just('\\')
+            .or(just('"'))
+            .or(just('\''))
+            .or(just('n').to('\n'))
+            .or(just('r').to('\r'))
+            .or(just('t').to('\t'))
+            .or(just('0').to('\0'))
+            .or(unicode_escape),
+    );
+
     // A parser for strings
     let str_ = just('"')
-        .ignore_then(filter(|c| *c != '"').repeated())
+        .ignore_then(filter(|c| *c != '\\' && *c != '"').or(escape).repeated())
         .then_ignore(just('"'))
         .collect::<String>()
         .map(Token::Str);
 
-    // A parser for operators
-    let op = one_of("+-*/!=<>")
-        .repeated()
-        .at_least(1)
-        .collect::<String>()
+    // A parser for operators. the two character operators are tried first,
+    // so that something like a<=b doesn't lex as '<' followed by '='
+    let op = just("==")
+        .or(just("!="))
+        .or(just("<="))
+        .or(just(">="))
+        .or(just("&&"))
+        .or(just("||"))
+        .or(just("->"))
+        .or(just("=>"))
+        .or(just("+="))
+        .or(just("-="))
+        .or(just("*="))
+        .or(just("/="))
+        .or(just("++"))
+        .or(just("--"))
+        .map(|s: &str| s.to_string())
+        .or(one_of("+-*/!=<>&").map(|c: char| c.to_string()))
         .map(Token::Op);
 
+    // The range operator, as in 0..length
+    let range = just("..").map(|s: &str| Token::Op(s.to_string()));
+
     // A parser for control characters (delimiters, semicolons, etc.)
-    let ctrl = one_of("()[]{};,:<>").map(|c| Token::Ctrl(c));
+    let ctrl = one_of("()[]{};,:.").map(Token::Ctrl);
 
     // A parser for identifiers and keywords
     let ident = text::ident().map(|ident: String| match ident.as_str() {
@@ -37,6 +81,17 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
         "as" => Token::As,
         "if" => Token::If,
         "else" => Token::Else,
+        "extern" => Token::Extern,
+        "while" => Token::While,
+        "for" => Token::For,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "new" => Token::New,
+        "delete" => Token::Delete,
+        "static" => Token::Static,
+        "union" => Token::Union,
+        "is" => Token::Is,
+        "match" => Token::Match,
         "true" => Token::Bool(true),
         "false" => Token::Bool(false),
         "null" => Token::Null,
@@ -46,6 +101,7 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
     // A single token can be one of the above
     let token = num
         .or(str_)
+        .or(range)
         .or(ctrl)
         .or(op)
         .or(ident)
@@ -8,14 +8,16 @@ use chumsky::{prelude::*, Stream};
 use ariadne::{Color, Fmt, Label, Report, ReportKind, Source};
 use inkwell::OptimizationLevel;
 use inkwell::context::Context;
+use inkwell::builder::Builder;
 use inkwell::module::Module;
 use inkwell::targets::{TargetMachine, Target, InitializationConfig, RelocMode, CodeModel, FileType};
 use inkwell::types::IntType;
 use inkwell::values::PointerValue;
 use inkwell::passes::PassManager;
 
+use std::cell::RefCell;
 use std::path::Path;
-use std::{collections::HashMap, env, fmt, fs};
+use std::{collections::HashMap, collections::HashSet, env, fmt, fs};
 
 //use ariadne:;
 
@@ -23,10 +25,21 @@ pub mod parser;
 pub mod lexer;
 pub mod compile;
 pub mod ast;
+pub mod typecheck;
+pub mod runtime;
+pub mod instantiate;
+
+#[cfg(test)]
+mod tests;
 
 use crate::parser::program_parser;
 use crate::lexer::lexer;
 use crate::compile::Compiler;
+use crate::typecheck::TypeChecker;
+use crate::typecheck::Inferred;
+use crate::instantiate::name_instances;
+use crate::instantiate::is_unmade_instance;
+use crate::instantiate::owner_name;
 
 use crate::ast::Token;
 // use crate::AST::Spanned;
@@ -78,40 +91,261 @@ fn get_native_target_machine() -> TargetMachine {
         .unwrap()
 }
 
-fn main() {
-    env::set_var("RUST_BACKTRACE", "1");
+/// The errors for something that is defined again, which point at both of its definitions.
+fn redefinition_errors(name: &str, first: &ast::Span, again: &ast::Span) -> [Error; 2] {
+    [
+        Error {
+            span: again.clone(),
+            msg: format!("{} is defined more than once", name),
+        },
+        Error {
+            span: first.clone(),
+            msg: format!("{} is first defined here", name),
+        },
+    ]
+}
 
-    print_splash();
+/// The errors for a function that is defined again. the functions that come with the language have no
+/// definition in the source to point at, so reusing one of their names is reported on its own
+fn function_redefinition_errors(name: &str, first: &FunctionSignature, again: &ast::Span) -> Vec<Error> {
+    if runtime::signatures().iter().any(|(symbol, _)| symbol == name) {
+        return vec![Error {
+            span: again.clone(),
+            msg: format!("{} is reserved by the prelude, so cannot be defined again", name),
+        }];
+    }
+
+    redefinition_errors(name, &first.span, again).to_vec()
+}
 
-    // let src = fs::read_to_string(env::args().nth(1).expect("Expected file argument"))
-    //     .expect("Failed to read file");
+/// Whether an extern function is one that comes with the language, with the same parameter and return types.
+fn is_runtime_extern(name: &str, signature: &FunctionSignature) -> bool {
+    runtime::signatures().iter().any(|(symbol, runtime_signature)| {
+        symbol == name
+            && runtime_signature.return_type == signature.return_type
+            && runtime_signature.params.iter().map(|(_, typ)| typ).eq(signature.params.iter().map(|(_, typ)| typ))
+    })
+}
 
-     let src = fs::read_to_string(env::args().nth(1).unwrap_or("data/testProgram.aph".into()))
-         .expect("Failed to read file");
+/// Adds a unit to the functions and classes that the program is checked against, reporting any it redefines.
+fn map_unit(
+    unit: &ast::ProgramUnit,
+    func_map: &mut HashMap<String, FunctionSignature>,
+    class_map: &mut HashMap<String, ast::Class>,
+    union_map: &mut HashMap<String, ast::Union>,
+    errs: &mut Vec<Error>,
+) {
+    //classes and unions are both types, so they can't share a name
+    let first_type = |name: &str| class_map.get(name).map(|class| class.span.clone())
+        .or_else(|| union_map.get(name).map(|union| union.span.clone()));
+
+    match unit {
+        ast::ProgramUnit::Class(class) => {
+            match first_type(&class.name) {
+                Some(first) => errs.extend(redefinition_errors(&class.name, &first, &class.span)),
+                None => {
+                    class_map.insert(class.name.clone(), class.clone());
+                },
+            }
+        },
+        ast::ProgramUnit::Union(union) => {
+            match first_type(&union.name) {
+                Some(first) => errs.extend(redefinition_errors(&union.name, &first, &union.span)),
+                None => {
+                    union_map.insert(union.name.clone(), union.clone());
+                },
+            }
+        },
+        ast::ProgramUnit::Function(function) => {
+            let symbol = function.symbol();
+            let signature = &function.definition.signature;
+            if let Some(first) = func_map.insert(symbol.clone(), signature.clone()) {
+                errs.extend(function_redefinition_errors(&symbol, &first, &signature.span));
+            }
+        },
+        ast::ProgramUnit::Extern(function) => {
+            //an extern of a function that comes with the language, such as exit, declares the same function if it agrees with it
+            if is_runtime_extern(&function.name, &function.signature) {
+                return;
+            }
 
-    let context = inkwell::context::Context::create();
-    let module = context.create_module("main");
-    let builder = context.create_builder();
+            if let Some(first) = func_map.insert(function.name.clone(), function.signature.clone()) {
+                errs.extend(function_redefinition_errors(&function.name, &first, &function.signature.span));
+            }
+        },
+    }
+}
 
-    let pass_manager = PassManager::<Module>::create(());
+/// Infers the return types of the functions that don't write them, before anything is checked, as calls of them
+/// need to know what they return. a function that calls one whose return type isn't known yet waits for it, so
+/// they are inferred in the order that they call each other. functions whose return types can't be inferred are
+/// reported and left out of the rest of the checking
+fn infer_return_types(
+    program_units: &mut Vec<ast::ProgramUnit>,
+    func_map: &mut HashMap<String, FunctionSignature>,
+    class_map: &HashMap<String, ast::Class>,
+    union_map: &HashMap<String, ast::Union>,
+    class_templates: &HashMap<String, ast::ClassTemplate>,
+    errs: &mut Vec<Error>,
+) {
+    let mut uninferred: Vec<ast::NamedFunction> = program_units
+        .iter()
+        .filter_map(|unit| match unit {
+            ast::ProgramUnit::Function(function) if function.definition.signature.infers_return => Some(function.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut failed: HashSet<String> = HashSet::new();
+
+    while !uninferred.is_empty() {
+        //the functions that are waiting, and the symbols of the ones they are waiting for
+        let mut waiting: Vec<(ast::NamedFunction, String)> = Vec::new();
+        let mut progressed = false;
+
+        for function in uninferred.drain(..) {
+            let symbol = function.symbol();
+            let mut type_checker = TypeChecker::new(func_map, class_map, union_map);
+            let result = type_checker.infer_return_type(&symbol, &function.name, &function.definition);
+
+            //the rest of the instances of generic classes are only made as the functions that use them are checked
+            let missing_instance = type_checker.class_instances
+                .iter()
+                .find(|(template, type_args)| is_unmade_instance(template, type_args, class_templates, class_map));
+            let result = match (result, missing_instance) {
+                (Err(_), Some((template, type_args))) => Err(vec![Error {
+                    span: function.definition.signature.span.clone(),
+                    msg: format!(
+                        "the return type of {} can't be inferred, as it uses {}, which is only made once the program is checked; write it, as in -> i32",
+                        function.name, ast::Type::Generic(template.clone(), type_args.clone()),
+                    ),
+                }]),
+                (result, _) => result,
+            };
 
-    pass_manager.add_instruction_combining_pass();
-    pass_manager.add_reassociate_pass();
-    pass_manager.add_gvn_pass();
-    pass_manager.add_cfg_simplification_pass();
-    pass_manager.add_basic_alias_analysis_pass();
-    pass_manager.add_promote_memory_to_register_pass();
-    pass_manager.add_instruction_combining_pass();
-    pass_manager.add_reassociate_pass();
+            let return_type = match result {
+                Ok(Inferred::Known(return_type)) => return_type,
+                Ok(Inferred::Waiting(awaited, first)) => {
+                    waiting.push((function, awaited));
+                    //a function that is waiting can still tell the ones it calls what it returns, once one of its returns
+                    //has said so, which is how functions that call each other are inferred
+                    match first {
+                        Some(first) if func_map.get(&symbol).is_some_and(|signature| signature.infers_return) => first,
+                        _ => continue,
+                    }
+                },
+                Err(errors) => {
+                    errs.extend(errors);
+                    failed.insert(symbol.clone());
+                    ast::Type::Void
+                },
+            };
 
-    let compiler = Compiler {
-        context: &context,
-        builder: &builder,
-        module: &module,
-    };
+            progressed = true;
+            if let Some(signature) = func_map.get_mut(&symbol) {
+                signature.return_type = return_type;
+                signature.infers_return = false;
+            }
+        }
+
+        //the rest are waiting for each other, so they would wait forever
+        if !progressed {
+            return_type_cycle_errors(&waiting, errs);
+            for (function, _) in waiting.drain(..) {
+                let symbol = function.symbol();
+                if let Some(signature) = func_map.get_mut(&symbol) {
+                    signature.infers_return = false;
+                }
+                failed.insert(symbol);
+            }
+        }
+
+        uninferred.extend(waiting.into_iter().map(|(function, _)| function));
+    }
+
+    program_units.retain(|unit| !matches!(unit, ast::ProgramUnit::Function(function) if failed.contains(&function.symbol())));
+
+    for unit in program_units.iter_mut() {
+        if let ast::ProgramUnit::Function(function) = unit {
+            let inferred = func_map.get(&function.symbol()).filter(|_| function.definition.signature.infers_return);
+            if let Some(inferred) = inferred {
+                function.definition.signature.return_type = inferred.return_type.clone();
+                function.definition.signature.infers_return = false;
+            }
+        }
+    }
+}
+
+/// Reports the functions whose return types can't be inferred, as they call each other, or themselves,
+/// before any of their returns say what they return. those that are only waiting for them aren't reported
+fn return_type_cycle_errors(waiting: &[(ast::NamedFunction, String)], errs: &mut Vec<Error>) {
+    let awaited: HashMap<String, &String> = waiting.iter().map(|(function, awaited)| (function.symbol(), awaited)).collect();
+    let names: HashMap<String, &String> = waiting.iter().map(|(function, _)| (function.symbol(), &function.name)).collect();
+
+    for (function, _) in waiting {
+        let symbol = function.symbol();
+
+        //follows what the function waits for, and what that waits for in turn, to see whether it comes back around to it
+        let mut cycle = vec![&symbol];
+        let on_cycle = loop {
+            match awaited.get(*cycle.last().unwrap()) {
+                Some(&next) if *next == symbol => break true,
+                Some(&next) if !cycle.contains(&next) => cycle.push(next),
+                _ => break false,
+            }
+        };
+
+        if !on_cycle {
+            continue;
+        }
+
+        let msg = match cycle.as_slice() {
+            [_] => format!("the return type of {} can't be inferred, as it calls itself before any of its returns; write it, as in -> i32", function.name),
+            _ => {
+                let mut in_cycle: Vec<&str> = cycle.iter().map(|symbol| names[*symbol].as_str()).collect();
+                in_cycle.sort();
+                let (last, rest) = in_cycle.split_last().unwrap();
+                format!(
+                    "the return types of {} and {} can't be inferred, as they call each other before any of their returns; write one of them, as in -> i32",
+                    rest.join(", "), last,
+                )
+            },
+        };
+
+        errs.push(Error {
+            span: function.definition.signature.span.clone(),
+            msg,
+        });
+    }
+}
+
+/// Creates a compiler that builds code into the given module, for the target of the given machine.
+fn create_compiler<'a, 'ctx>(
+    context: &'ctx Context,
+    builder: &'a Builder<'ctx>,
+    module: &'a Module<'ctx>,
+    target_machine: &TargetMachine,
+) -> Compiler<'a, 'ctx> {
+    Compiler {
+        context,
+        builder,
+        module,
+        classes: RefCell::new(HashMap::new()),
+        unions: RefCell::new(HashMap::new()),
+        usize_type: ptr_sized_int_type(target_machine, context),
+        loop_stack: RefCell::new(Vec::new()),
+        scope_stack: RefCell::new(Vec::new()),
+        assigned_places: RefCell::new(Vec::new()),
+        temporaries: RefCell::new(Vec::new()),
+        moved: RefCell::new(Vec::new()),
+    }
+}
 
+/// Parses, checks and compiles a program into the compiler's module.
+/// returns the errors found lexing and parsing the program, and those found checking and compiling it
+fn compile_program(compiler: &Compiler, src: &str) -> (Vec<Simple<String>>, Vec<Error>) {
+    let (tokens, errs) = lexer().parse_recovery(src);
 
-    let (tokens, errs) = lexer().parse_recovery(src.as_str());
+    let mut semantic_errs: Vec<Error> = Vec::new();
 
     let parse_errs = if let Some(tokens) = tokens {
         //dbg!(tokens.clone());
@@ -122,62 +356,267 @@ fn main() {
         //dbg!(ast.clone());
 
 
-        if let Some(program_units) = ast.filter(|_| errs.len() + parse_errs.len() == 0) {
+        if let Some(mut program_units) = ast.filter(|_| errs.len() + parse_errs.len() == 0) {
 
-            let mut func_map: HashMap<String, FunctionSignature> = HashMap::new();
-            let mut variable_map: HashMap<String, PointerValue> = HashMap::new();
+            //methods can be defined outside of their class, so each class is told about the ones that are
+            let methods: Vec<(ast::Type, String)> = program_units
+                .iter()
+                .filter_map(|unit| match unit {
+                    ast::ProgramUnit::Function(function)
+                        if function.name != "new" && function.name != "delete" && !function.definition.signature.is_static => {
+                        function.definition.signature.owner.clone().map(|owner| (owner, function.name.clone()))
+                    },
+                    _ => None,
+                })
+                .collect();
+
+            for unit in program_units.iter_mut() {
+                if let ast::ProgramUnit::Class(class) = unit {
+                    for (_owner, method) in methods.iter().filter(|(owner, _)| owner_name(owner) == Some(&class.name)) {
+                        if !class.methods.contains(method) {
+                            class.methods.push(method.clone());
+                        }
+                    }
+                }
+            }
+
+            //generic classes are templates, which are only checked and compiled as the instances of them that are used.
+            //the functions that belong to a generic class are part of its template
+            let mut class_templates: HashMap<String, ast::ClassTemplate> = HashMap::new();
+            for unit in program_units.iter() {
+                if let ast::ProgramUnit::Class(class) = unit {
+                    if class.generic_params.is_empty() {
+                        continue;
+                    }
+
+                    match class_templates.get(&class.name) {
+                        Some(first) => semantic_errs.extend(redefinition_errors(&class.name, &first.class.span, &class.span)),
+                        None => {
+                            class_templates.insert(class.name.clone(), ast::ClassTemplate { class: class.clone(), functions: Vec::new() });
+                        },
+                    }
+                }
+            }
+
+            program_units.retain(|unit| match unit {
+                ast::ProgramUnit::Class(class) => class.generic_params.is_empty(),
+                ast::ProgramUnit::Function(function) => {
+                    match function.definition.signature.owner.as_ref().and_then(owner_name) {
+                        Some(owner) if class_templates.contains_key(owner) => {
+                            class_templates.get_mut(owner).unwrap().functions.push(function.clone());
+                            false
+                        },
+                        _ => true,
+                    }
+                },
+                ast::ProgramUnit::Union(_) | ast::ProgramUnit::Extern(_) => true,
+            });
+
+            let class_uses = name_instances(&mut program_units, &class_templates, &mut semantic_errs);
+
+            //the functions that come with the language can be used like any other
+            let mut func_map: HashMap<String, FunctionSignature> = runtime::signatures().into_iter().collect();
+            let mut class_map: HashMap<String, ast::Class> = HashMap::new();
+            let mut union_map: HashMap<String, ast::Union> = HashMap::new();
     
             //first pass, map all program units into their respective maps
-            for unit in program_units.clone() {
-                match unit {
-                    ast::ProgramUnit::Class(_) => {
-                        ()//dont do anything with class yet
+            for unit in program_units.iter() {
+                map_unit(unit, &mut func_map, &mut class_map, &mut union_map, &mut semantic_errs);
+            }
+
+            for class_template in class_templates.values() {
+                let name = &class_template.class.name;
+                let first = class_map.get(name).map(|class| &class.span).or_else(|| union_map.get(name).map(|union| &union.span));
+                if let Some(first) = first {
+                    semantic_errs.extend(redefinition_errors(name, first, &class_template.class.span));
+                }
+            }
+
+            //generic functions are templates, which are only checked and compiled as the instances that calls make of them
+            let mut templates: HashMap<String, ast::NamedFunction> = HashMap::new();
+            program_units.retain(|unit| match unit {
+                ast::ProgramUnit::Function(function) if !function.definition.signature.generic_params.is_empty() => {
+                    templates.insert(function.symbol(), function.clone());
+                    false
+                },
+                _ => true,
+            });
+
+            //the return types of generic functions depend on what their type parameters stand for, so they have to be written
+            let generic_functions = templates
+                .values_mut()
+                .chain(class_templates.values_mut().flat_map(|class_template| class_template.functions.iter_mut()));
+            for function in generic_functions.filter(|function| function.definition.signature.infers_return) {
+                let signature = &mut function.definition.signature;
+                semantic_errs.push(Error {
+                    span: signature.span.clone(),
+                    msg: match &signature.owner {
+                        Some(owner) => format!("{} belongs to the generic class {}, so its return type has to be written, as in -> T", function.name, owner),
+                        None => format!("{} is generic, so its return type has to be written, as in -> T", function.name),
                     },
+                });
+                signature.infers_return = false;
+            }
+
+            //second pass, type check everything before any IR is built
+            let type_checker = TypeChecker::new(&func_map, &class_map, &union_map);
+            for template in templates.values() {
+                if let Err(error) = type_checker.check_generic_params(&template.definition.signature) {
+                    semantic_errs.push(error);
+                }
+            }
+
+            for class_template in class_templates.values() {
+                let errors = std::iter::once(type_checker.check_class_generic_params(&class_template.class))
+                    .chain(class_template.functions
+                        .iter()
+                        .filter(|function| !function.definition.signature.generic_params.is_empty())
+                        .map(|function| type_checker.check_generic_params(&function.definition.signature)));
+
+                semantic_errs.extend(errors.filter_map(Result::err));
+            }
+
+            let (checked_units, errors) = instantiate::check_in_rounds(
+                program_units,
+                class_uses,
+                &templates,
+                &class_templates,
+                &mut func_map,
+                &mut class_map,
+                &mut union_map,
+            );
+            program_units = checked_units;
+            semantic_errs.extend(errors);
+
+            //only a well-typed program is compiled
+            let well_typed = semantic_errs.is_empty();
+
+            //declare every class and union before laying any of them out, as fields can be of any of them
+            for unit in program_units.iter().filter(|_| well_typed) {
+                match unit {
+                    ast::ProgramUnit::Class(class) => compiler.declare_class(class),
+                    ast::ProgramUnit::Union(union) => compiler.declare_union(union),
+                    _ => (),
+                }
+            }
+
+            for unit in program_units.iter().filter(|_| well_typed) {
+                if let ast::ProgramUnit::Class(class) = unit {
+                    compiler.define_class(class);
+                    compiler.define_static_fields(class);
+                }
+            }
+
+            if well_typed {
+                compiler.define_unions();
+            }
+
+            //declare every function so calls can refer to functions defined later on
+            for unit in program_units.iter().filter(|_| well_typed) {
+                match unit {
+                    ast::ProgramUnit::Class(_) | ast::ProgramUnit::Union(_) => (),
                     ast::ProgramUnit::Function(function) => {
-                        func_map.insert(function.name, function.definition.signature.clone());
+                        compiler.declare_function(&function.symbol(), &function.definition.signature);
                     },
+                    ast::ProgramUnit::Extern(function) => {
+                        compiler.declare_extern(&function.name, &function.signature);
+                    },
+                }
+            }
+
+            if well_typed {
+                compiler.define_runtime();
+            }
+
+            //vtables point at methods, so they are built once every method is declared
+            for unit in program_units.iter().filter(|_| well_typed) {
+                if let ast::ProgramUnit::Class(class) = unit {
+                    compiler.define_vtable(class);
                 }
             }
 
-            for unit in program_units {
+            for unit in program_units.into_iter().filter(|_| well_typed) {
                 match unit {
                     ast::ProgramUnit::Class(class) => {
                         //todo!()
                     },
+                    ast::ProgramUnit::Union(_) => {
+                        //laid out already, there is no code to compile
+                    },
                     ast::ProgramUnit::Function(function) => {
                         println!("compiling {}...", function.name);
-                        let result = compiler.compile_function(&function.name, &function.definition, &func_map, &mut variable_map);
+                        let result = compiler.compile_function(&function.symbol(), &function.definition, &func_map);
     
                         match result {
                             Ok(result) => (),
-                            Err(error) => println!("{}", error.msg.as_str())
+                            Err(error) => semantic_errs.push(error),
                         };
     
                     },
+                    ast::ProgramUnit::Extern(_) => {
+                        //already declared, there is no body to compile
+                    },
                 }
                 //println!("compiling {}...", unit.name);
             }
         }
 
-        pass_manager.run_on(&module);
-        module.print_to_stderr();
-
-        module.write_bitcode_to_path(Path::new("testoutput.bc"));
-        
-
-
-        let target_machine = get_native_target_machine();
-        apply_target_to_module(&target_machine, &module);
-        target_machine.write_to_file(&module, FileType::Object, Path::new("output.o"));
-
         parse_errs
     } else {
         Vec::new()
     };
 
-    errs.into_iter()
+    let errs = errs.into_iter()
         .map(|e| e.map(|c| c.to_string()))
         .chain(parse_errs.into_iter().map(|e| e.map(|tok| tok.to_string())))
+        .collect();
+
+    (errs, semantic_errs)
+}
+
+fn main() {
+    env::set_var("RUST_BACKTRACE", "1");
+
+    print_splash();
+
+    // let src = fs::read_to_string(env::args().nth(1).expect("Expected file argument"))
+    //     .expect("Failed to read file");
+
+     let src = fs::read_to_string(env::args().nth(1).unwrap_or("data/testProgram.aph".into()))
+         .expect("Failed to read file");
+
+    let context = inkwell::context::Context::create();
+    let module = context.create_module("main");
+    let builder = context.create_builder();
+
+    let target_machine = get_native_target_machine();
+    apply_target_to_module(&target_machine, &module);
+
+    let pass_manager = PassManager::<Module>::create(());
+
+    pass_manager.add_instruction_combining_pass();
+    pass_manager.add_reassociate_pass();
+    pass_manager.add_gvn_pass();
+    pass_manager.add_cfg_simplification_pass();
+    pass_manager.add_basic_alias_analysis_pass();
+    pass_manager.add_promote_memory_to_register_pass();
+    pass_manager.add_instruction_combining_pass();
+    pass_manager.add_reassociate_pass();
+
+    let compiler = create_compiler(&context, &builder, &module, &target_machine);
+
+    let (errs, semantic_errs) = compile_program(&compiler, &src);
+
+    pass_manager.run_on(&module);
+    module.print_to_stderr();
+
+    module.write_bitcode_to_path(Path::new("testoutput.bc"));
+
+
+
+    target_machine.write_to_file(&module, FileType::Object, Path::new("output.o"));
+
+    errs.into_iter()
         .for_each(|e| {
             let report = Report::build(ReportKind::Error, (), e.span().start);
 
@@ -244,4 +683,18 @@ fn main() {
 
             report.finish().print(Source::from(&src)).unwrap();
         });
+
+    semantic_errs.into_iter()
+        .for_each(|e| {
+            Report::build(ReportKind::Error, (), e.span.start)
+                .with_message(&e.msg)
+                .with_label(
+                    Label::new(e.span)
+                        .with_message(format!("{}", e.msg.fg(Color::Red)))
+                        .with_color(Color::Red),
+                )
+                .finish()
+                .print(Source::from(&src))
+                .unwrap();
+        });
 }
@@ -6,76 +6,162 @@ use crate::ast;
 use crate::ast::Span;
 use crate::ast::Token;
 use crate::ast::Class;
+use crate::ast::Field;
 use crate::ast::FunctionSignature;
 use crate::ast::NamedFunction;
 use crate::ast::FunctionDefinition;
+use crate::ast::ExternFunction;
 use crate::ast::Expr;
+use crate::ast::ForHeader;
 use crate::ast::Value;
 use crate::ast::BinaryOp;
+use crate::ast::UnaryOp;
 use crate::ast::ProgramUnit;
+use crate::ast::Union;
+use crate::ast::Variant;
+use crate::ast::Pattern;
+use crate::ast::MatchArm;
+use crate::ast::Lambda;
+use crate::ast::Capture;
+use crate::ast::Type;
 
 use crate::ast::Spanned;
 
 
-pub fn function_declaration_parser() -> impl Parser<Token, (String, FunctionSignature), Error=Simple<Token>> + Clone {
-    let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
+//parse the name of a type, without any pointer suffix.
+//a generic class is given its type arguments after its name, as in List<i32*>,
+//and a function type its parameter types and what it returns, as in fn(i32, f64) -> bool
+pub fn type_name_parser() -> impl Parser<Token, Type, Error = Simple<Token>> + Clone {
+    recursive(|type_name| {
+        let typ = type_name
+            .then(just(Token::Op("*".to_string())).repeated())
+            .foldl(|pointee, _| Type::Pointer(Box::new(pointee)));
+
+        // without a return type, the function returns nothing
+        let function = just(Token::Fn)
+            .ignore_then(typ
+                .clone()
+                .separated_by(just(Token::Ctrl(',')))
+                .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))))
+            .then(just(Token::Op("->".to_string())).ignore_then(typ.clone()).or_not())
+            .map(|(params, return_type)| Type::Function(params, Box::new(return_type.unwrap_or(Type::Void))));
+
+        select! { Token::Ident(ident) => ident }
+            .then(type_args_parser(typ).or_not())
+            .map(|(name, type_args)| match type_args {
+                Some(type_args) => Type::Generic(name, type_args),
+                None => Type::from_name(&name),
+            })
+            .or(function)
+    })
+    .labelled("type")
+}
+
+//parse a type, which can be a pointer to another type, as in Base* or i32**
+pub fn type_parser() -> impl Parser<Token, Type, Error = Simple<Token>> + Clone {
+    type_name_parser()
+        .then(just(Token::Op("*".to_string())).repeated())
+        .foldl(|pointee, _| Type::Pointer(Box::new(pointee)))
+        .labelled("type")
+}
+
+//parse the type arguments given to something generic, as the <i32, f64> in Pair<i32, f64>
+fn type_args_parser(typ: impl Parser<Token, Type, Error = Simple<Token>> + Clone) -> impl Parser<Token, Vec<Type>, Error = Simple<Token>> + Clone {
+    typ.separated_by(just(Token::Ctrl(',')))
+        .at_least(1)
+        .delimited_by(just(Token::Op("<".to_string())), just(Token::Op(">".to_string())))
+        .labelled("type arguments")
+}
 
-    let template_list = ident.clone()
+//parse the type parameters of something generic, as the <T, U> in fn swap<T, U>
+fn type_params_parser() -> impl Parser<Token, (Vec<String>, Vec<Span>), Error = Simple<Token>> + Clone {
+    select! { Token::Ident(ident) => ident }
+        .map_with_span(|name, span| (name, span))
         .separated_by(just(Token::Ctrl(',')))
         .delimited_by(
-            just(Token::Ctrl('<')),
-            just(Token::Ctrl('>'))
+            just(Token::Op("<".to_string())),
+            just(Token::Op(">".to_string()))
         )
-        .repeated().at_most(1)
-        .labelled("template type list");
+        //the spans of the parameters are kept alongside them, for errors about them
+        .map(|params| params.into_iter().unzip())
+        .labelled("template type list")
+}
+
+//parse the parameter list of a function, such as (a as i32, b as f64)
+pub fn function_params_parser() -> impl Parser<Token, (Vec<(String, Type)>, Vec<Span>), Error = Simple<Token>> + Clone {
+    let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
 
     let function_parameter = 
-        ident.clone()
-            .then_ignore(just(Token::Ctrl(':')))
-            .then(ident.clone());
+        ident
+            .then_ignore(just(Token::As))
+            .then(type_parser().map_with_span(|typ, span| (typ, span)));
             // .map(|name, typedef| {
             //     Variable {
             //         name: name
             //         returnType: typedef)
             // });
 
-    let params = function_parameter.clone()
+    function_parameter
         .separated_by(just(Token::Ctrl(',')))
         //.allow_trailing()
         .delimited_by(
             just(Token::Ctrl('(')),
             just(Token::Ctrl(')'))
         )
-        .labelled("function params");
+        //the spans of the parameters' types are kept alongside them, for errors about the types
+        .map(|params| params.into_iter().map(|(name, (typ, span))| ((name, typ), span)).unzip())
+        .labelled("function params")
+}
+
+pub fn function_declaration_parser() -> impl Parser<Token, (String, FunctionSignature), Error=Simple<Token>> + Clone {
+    let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
+
+    let params = function_params_parser();
+
+    // a method can be declared outside of the type it belongs to, as in fn i32: add(other as i32) -> i32
+    let owner = type_parser()
+        .then_ignore(just(Token::Ctrl(':')))
+        .labelled("method owner");
 
     let function_declaration = just(Token::Fn)
-        .ignore_then(ident.clone())
-        .then(template_list)
+        .ignore_then(owner.or_not())
+        .then(ident.clone())
+        .then(type_params_parser().or_not())
         .then(params)
-        .then_ignore(just(Token::Op("->".into())))
-        .then(ident.clone());
+        .then(just(Token::Static).or_not())
+        .then(just(Token::Op("->".into()))
+            .ignore_then(type_parser().map_with_span(|typ, span| (typ, span)))
+            .or_not());
 
-        function_declaration.map(| (((name, generic_params), params), return_type) | {
-            (
-                name,
-                FunctionSignature {
-                    return_type: return_type,
-                    generic_params:
-                        if generic_params.len() > 0 {
-                            generic_params[0].clone()
-                        } else {
-                            Vec::<String>::new()
-                        },
-                    params: params,
-                }
-            )        
+        function_declaration.map_with_span(| (((((owner, name), generic_params), (params, param_spans)), is_static), return_type), span: Span | {
+            // the return type can be left out, in which case it is inferred from the function's returns
+            let infers_return = return_type.is_none();
+            let (return_type, return_span) = return_type.unwrap_or((Type::Void, span.clone()));
+            let (generic_params, generic_param_spans) = generic_params.unwrap_or_default();
+
+            let signature = FunctionSignature {
+                return_type,
+                return_span,
+                infers_return,
+                generic_params,
+                generic_param_spans,
+                params,
+                param_spans,
+                span,
+                owner: None,
+                is_static: is_static.is_some(),
+            };
+
+            match owner {
+                Some(owner) => (name, signature.into_method(owner)),
+                None => (name, signature),
+            }
         })
 }
 
-pub fn function_definition_parser() -> impl Parser<Token, NamedFunction, Error = Simple<Token>> + Clone {
-    //let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
-
-    let function_body =  expression_parser()
+//parse the body of a function, in braces
+pub fn function_body_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
+    expression_parser()
     // .then(just(Token::Return))
     .delimited_by(
         just(Token::Ctrl('{')),
@@ -89,11 +175,15 @@ pub fn function_definition_parser() -> impl Parser<Token, NamedFunction, Error =
             (Token::Ctrl('['), Token::Ctrl(']')),
         ],
         |span| (Expr::Error, span),
-    ));
+    ))
+}
+
+pub fn function_definition_parser() -> impl Parser<Token, NamedFunction, Error = Simple<Token>> + Clone {
+    //let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
 
     let function_definition =
         function_declaration_parser()
-        .then(function_body)
+        .then(function_body_parser())
         .map(|((name, signature), body)| {
             NamedFunction {
                 name: name,
@@ -108,61 +198,384 @@ pub fn function_definition_parser() -> impl Parser<Token, NamedFunction, Error =
     function_definition
 }
 
+//parse a function that is defined elsewhere, e.g. in libc.
+//there is no body, just a signature terminated by a semicolon
+pub fn extern_parser() -> impl Parser<Token, ExternFunction, Error = Simple<Token>> + Clone {
+    just(Token::Extern)
+        .ignore_then(function_declaration_parser())
+        .then_ignore(just(Token::Ctrl(';')))
+        .map(|(name, mut signature)| {
+            // there are no returns to infer the return type from, so it returns nothing unless it says otherwise
+            signature.infers_return = false;
+            ExternFunction {
+                name,
+                signature,
+            }
+        })
+        .labelled("extern function")
+}
+
+/// Something declared in the body of a class
+#[derive(Debug, Clone)]
+enum ClassMember {
+    Field(Field),
+    Method(NamedFunction),
+}
+
+//constructors and destructors have no name or return type of their own.
+//they are named after the keyword that declares them, and return nothing
+fn special_method(name: &str, (params, param_spans): (Vec<(String, Type)>, Vec<Span>), body: Spanned<Expr>, span: Span) -> NamedFunction {
+    NamedFunction {
+        name: name.to_string(),
+        definition: FunctionDefinition {
+            signature: FunctionSignature {
+                return_type: Type::Void,
+                return_span: span.clone(),
+                infers_return: false,
+                params,
+                param_spans,
+                generic_params: Vec::new(),
+                generic_param_spans: Vec::new(),
+                span,
+                owner: None,
+                is_static: false,
+            },
+            body,
+        }
+    }
+}
+
+//parse a constructor, without the type it belongs to: new(x as i32) { ... }
+fn constructor_parser() -> impl Parser<Token, NamedFunction, Error = Simple<Token>> + Clone {
+    just(Token::New)
+        .ignore_then(function_params_parser())
+        .then(function_body_parser())
+        .map_with_span(|(params, body), span: Span| special_method("new", params, body, span))
+        .labelled("constructor")
+}
+
+//parse a destructor, without the type it belongs to: delete() { ... }
+fn destructor_parser() -> impl Parser<Token, NamedFunction, Error = Simple<Token>> + Clone {
+    just(Token::Delete)
+        .ignore_then(just(Token::Ctrl('(')).then(just(Token::Ctrl(')'))))
+        .ignore_then(function_body_parser())
+        .map_with_span(|body, span: Span| special_method("delete", (Vec::new(), Vec::new()), body, span))
+        .labelled("destructor")
+}
+
+//parse a constructor or destructor defined outside of its class, as in Base: new(param as i32) { ... }
+pub fn out_of_line_special_method_parser() -> impl Parser<Token, NamedFunction, Error = Simple<Token>> + Clone {
+    type_parser()
+        .then_ignore(just(Token::Ctrl(':')))
+        .then(constructor_parser().or(destructor_parser()))
+        .map(|(owner, mut function)| {
+            function.definition.signature = function.definition.signature.into_method(owner);
+            function
+        })
+}
+
 //parse the class.
 //outputs a list of tuple of (class, function defintion list)
 pub fn class_parser() -> impl Parser<Token, Vec<ProgramUnit>, Error = Simple<Token>> + Clone {
     let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
-    
-    let classDefinition = function_definition_parser()
-        .map(|function| {
-            ProgramUnit::Function(function)
+
+    // fieldA as i32; or, with a value it is initialised to, x as i32 = 2;
+    // static fields are shared by every instance: z as static i32 = 10;
+    let field = ident
+        .then_ignore(just(Token::As))
+        .then(just(Token::Static).or_not())
+        .then(type_parser())
+        .then(just(Token::Op("=".to_string())).ignore_then(raw_expression_parser()).or_not())
+        .then_ignore(just(Token::Ctrl(';')))
+        .map_with_span(|(((name, is_static), typ), initializer), span: Span| {
+            Field {
+                name,
+                typ,
+                initializer,
+                is_static: is_static.is_some(),
+                span,
+            }
         })
+        .labelled("field");
+
+    // : new(x as i32) { ... } and : delete() { ... }
+    let special_method = just(Token::Ctrl(':'))
+        .ignore_then(constructor_parser().or(destructor_parser()));
+    
+    let classDefinition = field
+        .map(ClassMember::Field)
+        .or(function_definition_parser().map(ClassMember::Method))
+        .or(special_method.map(ClassMember::Method))
         .repeated()
         .delimited_by(
             just(Token::Ctrl('{')),
             just(Token::Ctrl('}'))
         );
 
+    // class Derived -> Base { ... }
+    let base = just(Token::Op("->".to_string()))
+        .ignore_then(ident)
+        .labelled("base class");
+
+    // class List<T> { ... } is generic, and only the instances of it for each T are compiled
     let classDecl = 
         just(Token::Class)
         .ignore_then(ident)
-        .map(|name| {
-            ProgramUnit::Class(Class{
+        .then(type_params_parser().or_not())
+        .then(base.or_not())
+        .then(classDefinition.clone())
+        .map_with_span(|(((name, generic_params), base), members), span: Span| {
+            let (generic_params, generic_param_spans) = generic_params.unwrap_or_default();
+            let mut fields = Vec::new();
+            let mut methods = Vec::new();
+            let mut units = Vec::new();
+
+            for member in members {
+                match member {
+                    ClassMember::Field(field) => fields.push(field),
+                    // a method that names the type it belongs to is left as it is, even in a class
+                    ClassMember::Method(function) if function.definition.signature.owner.is_some() => {
+                        units.push(ProgramUnit::Function(function));
+                    },
+                    ClassMember::Method(mut function) => {
+                        if function.name != "new" && function.name != "delete" && !function.definition.signature.is_static {
+                            methods.push(function.name.clone());
+                        }
+
+                        function.definition.signature = function.definition.signature.into_method(Type::Named(name.clone()));
+                        units.push(ProgramUnit::Function(function));
+                    },
+                }
+            }
+
+            units.insert(0, ProgramUnit::Class(Class{
                 name: name,
-            })
-        })
-        .chain(classDefinition.clone())
-        .map(|funcs| {
-            funcs
+                base,
+                generic_params,
+                generic_param_spans,
+                instance_of: None,
+                fields,
+                methods,
+                span,
+            }));
+
+            units
         });
 
     classDecl
 }
 
+//parse a tagged union, whose variants can hold values of their own:
+//union Shape { Circle(f64); Rect(f64, f64); Empty; }
+pub fn union_parser() -> impl Parser<Token, Union, Error = Simple<Token>> + Clone {
+    let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
+
+    let variant = ident
+        .then(type_parser()
+            .separated_by(just(Token::Ctrl(',')))
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
+            .or_not())
+        .then_ignore(just(Token::Ctrl(';')))
+        .map_with_span(|(name, payload), span: Span| Variant {
+            name,
+            payload: payload.unwrap_or_default(),
+            span,
+        })
+        .labelled("variant");
+
+    just(Token::Union)
+        .ignore_then(ident)
+        .then(variant
+            .repeated()
+            .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))))
+        .map_with_span(|(name, variants), span: Span| Union {
+            name,
+            variants,
+            span,
+        })
+        .labelled("union")
+}
+
+//parses the pattern of a match arm: an integer or bool literal, _, a name, or a variant
+//along with patterns for the values it holds, as in Rect(w, _)
+fn pattern_parser() -> impl Parser<Token, Spanned<Pattern>, Error = Simple<Token>> + Clone {
+    recursive(|pattern| {
+        let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
+
+        let number = select! { Token::Num(n) => n }
+            .try_map(|n, span| parse_number(&n)
+                .map(Expr::Value)
+                .map_err(|msg| Simple::custom(span, msg)))
+            .map_with_span(|expr, span: Span| (expr, span));
+        let literal = just(Token::Op("-".to_string()))
+            .map_with_span(|_, span: Span| span)
+            .or_not()
+            .then(number)
+            .map(|(minus, number)| match minus {
+                Some(minus) => {
+                    let span = minus.start..number.1.end;
+                    (Expr::Unary(UnaryOp::Neg, Box::new(number)), span)
+                },
+                None => number,
+            })
+            .or(select! { Token::Bool(x) => Expr::Value(Value::Bool(x)) }.map_with_span(|expr, span: Span| (expr, span)))
+            .map(|literal| Pattern::Literal(Box::new(literal)));
+
+        let variant = ident
+            .then(pattern
+                .separated_by(just(Token::Ctrl(',')))
+                .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))))
+            .map(|(name, fields)| Pattern::Variant(name, fields, None));
+
+        let name = ident.map(|name| if name == "_" { Pattern::Wildcard } else { Pattern::Binding(name) });
+
+        literal
+            .or(variant)
+            .or(name)
+            .map_with_span(|pattern, span: Span| (pattern, span))
+            .labelled("pattern")
+    })
+}
+
 pub fn program_parser() -> impl Parser<Token, Vec<ProgramUnit>, Error = Simple<Token>> + Clone {
     class_parser()
+    .or(
+        union_parser()
+        .map(|union| {
+            vec![ProgramUnit::Union(union)]
+        })
+    )
     .or(
         function_definition_parser()
         .map(|function| {
             vec![ProgramUnit::Function(function)]
         })
     )
+    .or(
+        out_of_line_special_method_parser()
+        .map(|function| {
+            vec![ProgramUnit::Function(function)]
+        })
+    )
+    .or(
+        extern_parser()
+        .map(|function| {
+            vec![ProgramUnit::Extern(function)]
+        })
+    )
     .repeated()
     .then_ignore(end())
     .flatten()
 }
 
+/// Something that can follow an expression and apply to it, like the arguments of a call
+#[derive(Debug, Clone)]
+enum Postfix {
+    Call(Vec<Spanned<Expr>>),
+    Field(String),
+    Index(Spanned<Expr>),
+}
+
+/// Whether an expression ends in a block, and so can be used as a statement without a trailing semicolon.
+fn is_block_like(expr: &Expr) -> bool {
+    matches!(expr, Expr::Block(_) | Expr::If(..) | Expr::Match(..) | Expr::While(..) | Expr::For(..))
+}
+
+/// Works out the value of a number literal from the text the lexer produced, such as 0xFF_FFi64 or 1.5f32.
+fn parse_number(literal: &str) -> Result<Value, String> {
+    let text = literal.replace('_', "");
+
+    let (radix, text) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, &text[..]),
+    };
+
+    // the suffix starts at the first character that can't be a digit.
+    // hex digits include 'f', so hex literals can't have a float suffix
+    let suffix_start = text
+        .find(|c: char| if radix == 16 { !c.is_ascii_hexdigit() } else { c.is_alphabetic() })
+        .unwrap_or(text.len());
+    let (digits, suffix) = text.split_at(suffix_start);
+
+    let suffix = match suffix {
+        "" => None,
+        suffix => match Type::from_name(suffix) {
+            typ if typ.is_numeric() => Some(typ),
+            _ => return Err(format!("unknown number suffix '{}'", suffix)),
+        },
+    };
+
+    if radix == 10 && digits.contains('.') {
+        if suffix.as_ref().is_some_and(|typ| !typ.is_float()) {
+            return Err(format!("float literal {} cannot have an integer suffix", literal));
+        }
+
+        return match digits.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(Value::Float(x, suffix)),
+            _ => Err(format!("float literal {} is too large", literal)),
+        };
+    }
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(format!("invalid digits in number literal {}", literal));
+    }
+
+    match u64::from_str_radix(digits, radix) {
+        Ok(n) => Ok(Value::Int(n, suffix)),
+        Err(_) => Err(format!("integer literal {} is too large", literal)),
+    }
+}
+
+//parses the contents of a block: a list of statements, optionally followed by
+//an expression without a semicolon that gives the block its value
 pub fn expression_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
+    expression_parsers().1
+}
+
+//parses a single expression, without any statements, such as the initial value of a field
+pub fn raw_expression_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
+    expression_parsers().0
+}
+
+//builds the parsers for a single expression and for the contents of a block together,
+//as each of them can contain the other
+fn expression_parsers() -> (
+    impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone,
+    impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone,
+) {
     let ident = select! { Token::Ident(ident) => ident.clone() }.labelled("identifier");
 
-    
+    let mut single_expr = Recursive::declare();
+
+    let block_body = recursive(|block_body| {
+        // A block is a list of statements in braces, with its own scope
+        let block = block_body
+            .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
+            .map_with_span(|body, span: Span| (Expr::Block(Box::new(body)), span))
+            // Attempt to recover anything that looks like a block but contains errors
+            .recover_with(nested_delimiters(
+                Token::Ctrl('{'),
+                Token::Ctrl('}'),
+                [
+                    (Token::Ctrl('('), Token::Ctrl(')')),
+                    (Token::Ctrl('['), Token::Ctrl(']')),
+                ],
+                |span| (Expr::Error, span),
+            ))
+            .labelled("block");
+
         let raw_expr = recursive(|raw_expr| {
             let val = select! {
                 Token::Null => Expr::Value(Value::Null),
                 Token::Bool(x) => Expr::Value(Value::Bool(x)),
-                Token::Num(n) => Expr::Value(Value::Num(n.parse().unwrap())),
                 Token::Str(s) => Expr::Value(Value::Str(s)),
             }
+            .or(select! { Token::Num(n) => n }
+                .try_map(|n, span| parse_number(&n)
+                    .map(Expr::Value)
+                    .map_err(|msg| Simple::custom(span, msg))))
             .labelled("value");
 
 
@@ -177,8 +590,60 @@ pub fn expression_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<T
                 .delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']')))
                 .map(Expr::List);
 
+            // new Derived(5, 3) constructs an instance on the heap, as does new List<i32>() for a generic class
+            let new = just(Token::New)
+                .ignore_then(ident
+                    .then(type_args_parser(type_parser()).or_not())
+                    .map_with_span(|(name, type_args), span: Span| match type_args {
+                        Some(type_args) => (Expr::GenericName(name, type_args), span),
+                        None => (Expr::Local(name), span),
+                    }))
+                .then(items.clone().delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))))
+                .map_with_span(|(class, args), span: Span| {
+                    let construct_span = class.1.start..span.end;
+                    Expr::New(Box::new((Expr::Call(Box::new(class), args), construct_span)))
+                });
+
+            // a generic function called with explicit type arguments, as in f<i32>(x).
+            // a name followed by < is only taken as one if a call follows, so that a < b is still a comparison
+            let generic_call = ident
+                .then(type_args_parser(type_parser()))
+                .map_with_span(|(name, type_args), span: Span| (Expr::GenericName(name, type_args), span))
+                .then(items.clone().delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))))
+                .map(|(func, args)| Expr::Call(Box::new(func), args));
+
+            // a closure, as in fn[count, &total](x as i32) -> i32 { x * count }. it can use the variables
+            // of the enclosing function in the brackets, the ones after & by reference. without a return type,
+            // it returns nothing
+            let capture = just(Token::Op("&".to_string()))
+                .or_not()
+                .then(ident)
+                .map_with_span(|(by_reference, name), span: Span| Capture {
+                    name,
+                    by_reference: by_reference.is_some(),
+                    typ: None,
+                    span,
+                });
+            let lambda = just(Token::Fn)
+                .ignore_then(capture
+                    .separated_by(just(Token::Ctrl(',')))
+                    .delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']')))
+                    .or_not())
+                .then(function_params_parser())
+                .then(just(Token::Op("->".to_string())).ignore_then(type_parser()).or_not())
+                .then(block.clone())
+                .map(|(((captures, (params, _)), return_type), body)| Expr::Lambda(Box::new(Lambda {
+                    captures: captures.unwrap_or_default(),
+                    params,
+                    return_type: return_type.unwrap_or(Type::Void),
+                    body,
+                })));
+
             // 'Atoms' are expressions that contain no ambiguity
             let atom = val
+                .or(new)
+                .or(generic_call)
+                .or(lambda)
                 .or(ident.map(Expr::Local))
 //                .or(list)
                 .map_with_span(|expr, span| (expr, span))
@@ -207,29 +672,88 @@ pub fn expression_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<T
                     |span| (Expr::Error, span),
                 ));
 
-            // Function calls have very high precedence so we prioritise them
+            // Function calls, field accesses and indexing have very high precedence so we prioritise them
             let call = atom
                 .then(
                     items
                         .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
-                        .map_with_span(|args, span: Span| (args, span))
+                        .map(Postfix::Call)
+                        .or(just(Token::Ctrl('.')).ignore_then(ident).map(Postfix::Field))
+                        .or(raw_expr
+                            .clone()
+                            .delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']')))
+                            .map(Postfix::Index))
+                        .map_with_span(|postfix, span: Span| (postfix, span))
                         .repeated(),
                 )
-                .foldl(|f, args| {
-                    let span = f.1.start..args.1.end;
-                    (Expr::Call(Box::new(f), args.0), span)
+                .foldl(|a, (postfix, postfix_span)| {
+                    let span = a.1.start..postfix_span.end;
+                    match postfix {
+                        // calling a field, as in a.b(), is a method call
+                        Postfix::Call(args) => match a.0 {
+                            Expr::Field(obj, name, _) => (Expr::MethodCall(obj, name, args, None), span),
+                            _ => (Expr::Call(Box::new(a), args), span),
+                        },
+                        Postfix::Field(name) => (Expr::Field(Box::new(a), name, None), span),
+                        Postfix::Index(index) => (Expr::Index(Box::new(a), Box::new(index)), span),
+                    }
+                });
+
+            // Unary ops (not, negate, dereference and address-of) bind tighter than any binary op
+            let op = just(Token::Op("!".to_string()))
+                .to(UnaryOp::Not)
+                .or(just(Token::Op("-".to_string())).to(UnaryOp::Neg))
+                .or(just(Token::Op("*".to_string())).to(UnaryOp::Deref))
+                .or(just(Token::Op("&".to_string())).to(UnaryOp::Ref))
+                .map_with_span(|op, span: Span| (op, span));
+            let unary = op
+                .repeated()
+                .then(call)
+                .foldr(|(op, op_span), a| {
+                    let span = op_span.start..a.1.end;
+                    (Expr::Unary(op, Box::new(a)), span)
+                });
+
+            // Casts bind tighter than any binary op, so a + b as i64 only casts b.
+            // the type can't be a pointer, so that a as f64 * b is a multiplication
+            let cast = unary
+                .then(
+                    just(Token::As)
+                        .ignore_then(type_name_parser())
+                        .map_with_span(|typ, span: Span| (typ, span))
+                        .repeated(),
+                )
+                .foldl(|a, (typ, typ_span)| {
+                    let span = a.1.start..typ_span.end;
+                    (Expr::Cast(Box::new(a), typ, None), span)
+                });
+
+            // Checking which variant a union holds binds as tightly as a cast, as in shape is Circle
+            let is = cast
+                .then(
+                    just(Token::Is)
+                        .ignore_then(ident)
+                        .map_with_span(|variant, span: Span| (variant, span))
+                        .or_not(),
+                )
+                .map(|(a, variant)| match variant {
+                    Some((variant, variant_span)) => {
+                        let span = a.1.start..variant_span.end;
+                        (Expr::Is(Box::new(a), variant, None), span)
+                    },
+                    None => a,
                 });
 
             // Product ops (multiply and divide) have equal precedence
             let op = just(Token::Op("*".to_string()))
                 .to(BinaryOp::Mul)
                 .or(just(Token::Op("/".to_string())).to(BinaryOp::Div));
-            let product = call
+            let product = is
                 .clone()
-                .then(op.then(call).repeated())
+                .then(op.then(is).repeated())
                 .foldl(|a, (op, b)| {
                     let span = a.1.start..b.1.end;
-                    (Expr::Binary(Box::new(a), op, Box::new(b)), span)
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
                 });
 
             // Sum ops (add and subtract) have equal precedence
@@ -241,89 +765,263 @@ pub fn expression_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<T
                 .then(op.then(product).repeated())
                 .foldl(|a, (op, b)| {
                     let span = a.1.start..b.1.end;
-                    (Expr::Binary(Box::new(a), op, Box::new(b)), span)
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
+                });
+
+            // Ordering ops (less-than, greater-than, etc.) have equal precedence
+            let op = just(Token::Op("<".to_string()))
+                .to(BinaryOp::Lt)
+                .or(just(Token::Op(">".to_string())).to(BinaryOp::Gt))
+                .or(just(Token::Op("<=".to_string())).to(BinaryOp::LtEq))
+                .or(just(Token::Op(">=".to_string())).to(BinaryOp::GtEq));
+            let ordering = sum
+                .clone()
+                .then(op.then(sum).repeated())
+                .foldl(|a, (op, b)| {
+                    let span = a.1.start..b.1.end;
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
                 });
 
             // Comparison ops (equal, not-equal) have equal precedence
             let op = just(Token::Op("==".to_string()))
                 .to(BinaryOp::Eq)
                 .or(just(Token::Op("!=".to_string())).to(BinaryOp::NotEq));
-            let compare = sum
+            let compare = ordering
                 .clone()
-                .then(op.then(sum).repeated())
+                .then(op.then(ordering).repeated())
+                .foldl(|a, (op, b)| {
+                    let span = a.1.start..b.1.end;
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
+                });
+
+            // Logical and binds tighter than logical or
+            let op = just(Token::Op("&&".to_string())).to(BinaryOp::And);
+            let logic_and = compare
+                .clone()
+                .then(op.then(compare).repeated())
                 .foldl(|a, (op, b)| {
                     let span = a.1.start..b.1.end;
-                    (Expr::Binary(Box::new(a), op, Box::new(b)), span)
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
                 });
 
-            compare
+            let op = just(Token::Op("||".to_string())).to(BinaryOp::Or);
+            let logic_or = logic_and
+                .clone()
+                .then(op.then(logic_and).repeated())
+                .foldl(|a, (op, b)| {
+                    let span = a.1.start..b.1.end;
+                    (Expr::Binary(Box::new(a), op, Box::new(b), None), span)
+                });
+
+            // if/else. the condition isn't parenthesised, but both branches must be blocks.
+            // 'else if' chains are nested ifs in the else branch
+            let if_ = recursive(|if_| {
+                just(Token::If)
+                    .ignore_then(raw_expr.clone())
+                    .then(block.clone())
+                    .then(just(Token::Else).ignore_then(block.clone().or(if_)).or_not())
+                    .map_with_span(|((cond, a), b), span: Span| {
+                        (
+                            Expr::If(
+                                Box::new(cond),
+                                Box::new(a),
+                                // without an else branch, nothing happens when the condition is false
+                                Box::new(b.unwrap_or((Expr::Value(Value::Null), span.end..span.end))),
+                            ),
+                            span,
+                        )
+                    })
+            });
+
+            // match x { 0 => a; 1, 2 => b; _ => { c } }. the arms are tried in order, and an arm
+            // whose body is a block doesn't need a semicolon after it
+            let arm = pattern_parser()
+                .separated_by(just(Token::Ctrl(',')))
+                .at_least(1)
+                .then_ignore(just(Token::Op("=>".to_string())))
+                .then(block
+                    .clone()
+                    .then_ignore(just(Token::Ctrl(';')).or_not())
+                    .or(raw_expr.clone().then_ignore(just(Token::Ctrl(';')))))
+                .map(|(patterns, body)| MatchArm { patterns, body });
+            let match_ = just(Token::Match)
+                .ignore_then(raw_expr.clone())
+                .then(arm.repeated().delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))))
+                .map_with_span(|(value, arms), span: Span| (Expr::Match(Box::new(value), arms, None), span));
+
+            // Block-like expressions are tried first, so that a statement such as an
+            // if/else is not continued by an operator on the next line
+            let block_expr = block
+                .clone()
+                .or(if_)
+                .or(match_);
+
+            // Assignment has the lowest precedence, and is right associative.
+            // a += b is sugar for a = a + b, and a++ for a += 1
+            let op = just(Token::Op("=".to_string()))
+                .to(None)
+                .or(just(Token::Op("+=".to_string())).to(Some(BinaryOp::Add)))
+                .or(just(Token::Op("-=".to_string())).to(Some(BinaryOp::Sub)))
+                .or(just(Token::Op("*=".to_string())).to(Some(BinaryOp::Mul)))
+                .or(just(Token::Op("/=".to_string())).to(Some(BinaryOp::Div)));
+            let step = just(Token::Op("++".to_string()))
+                .to(BinaryOp::Add)
+                .or(just(Token::Op("--".to_string())).to(BinaryOp::Sub))
+                .map_with_span(|op, span: Span| (Some(op), (Expr::Value(Value::Int(1, None)), span)));
+            let assign = logic_or
+                .clone()
+                .then(op.then(raw_expr.clone()).or(step).or_not())
+                .map(|(target, assignment)| match assignment {
+                    Some((op, val)) => {
+                        let span = target.1.start..val.1.end;
+                        let val = match op {
+                            Some(op) => {
+                                let current = (Expr::AssignedValue, target.1.clone());
+                                (Expr::Binary(Box::new(current), op, Box::new(val), None), span.clone())
+                            },
+                            None => val,
+                        };
+                        (Expr::Assign(Box::new(target), Box::new(val)), span)
+                    },
+                    None => target,
+                });
+
+            block_expr.or(assign)
         }); 
 
-     // A var statement
-     let var_statement = just(Token::Var)
-     .ignore_then(ident)
-     .then_ignore(just(Token::As))
-     .then(ident)
-     .then_ignore(just(Token::Op("=".to_string())))
-     .then(raw_expr.clone())
-     .then_ignore(just(Token::Ctrl(';')))
-     .map_with_span(|((name, typename), val), span: Span| {
-         (Expr::Var(name, typename, Box::new(val)), span)
-     });
-
-     let ret_statement = just(Token::Ret)
-     .ignore_then(raw_expr.clone())
-     .then_ignore(just(Token::Ctrl(';')))
-     .map_with_span(|val, span: Span| {
-         (Expr::Ret(Box::new(val)), span)
-     });
-
-
- let expression_statement = 
-     raw_expr.clone()
-     .then_ignore(just(Token::Ctrl(';')));
-
-  let statement = expression_statement.clone()
-      .or(var_statement.clone())
-      .or(ret_statement);
-
- // let block = expr
- // .clone()
- // .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
- // // Attempt to recover anything that looks like a block but contains errors
- // .recover_with(nested_delimiters(
- //     Token::Ctrl('{'),
- //     Token::Ctrl('}'),
- //     [
- //         (Token::Ctrl('('), Token::Ctrl(')')),
- //         (Token::Ctrl('['), Token::Ctrl(']')),
- //     ],
- //     |span| (Expr::Error, span),
- // ));
-
- // let block_expr = block
- //     //.or(if_)
- //     .labelled("block");
-
- // let block_chain = block_expr
- // .clone()
- // .then(block_expr.clone().repeated())
- // .foldl(|a, b| {
- //     let span = a.1.start..b.1.end;
- //     (Expr::Then(Box::new(a), Box::new(b)), span)
- // });
-
- 
- statement.clone()
-     .then(statement.clone().repeated())
-     .foldl(|a, b| {
-         let span = a.1.clone(); // TODO: Not correct
-         (
-             Expr::Then(
-                 Box::new(a),
-                 Box::new(b),
-             ),
-             span,
-         )
-     })
-}
\ No newline at end of file
+        single_expr.define(raw_expr.clone());
+
+        // A var declaration. the type can be left out, and is then deduced from the value.
+        // the value can be left out too, and the variable then gets its default value
+        let var_declaration = just(Token::Var)
+            .ignore_then(ident)
+            .then(just(Token::As).ignore_then(type_parser().map_with_span(|typ, span: Span| (typ, span))).or_not())
+            .then(just(Token::Op("=".to_string())).ignore_then(raw_expr.clone()).or_not())
+            .map_with_span(|((name, typename), val), span: Span| {
+                (Expr::Var(name, typename, val.map(Box::new)), span)
+            });
+
+        let var_statement = var_declaration
+            .clone()
+            .then_ignore(just(Token::Ctrl(';')));
+
+        let ret_statement = just(Token::Ret)
+            .ignore_then(raw_expr.clone())
+            .then_ignore(just(Token::Ctrl(';')))
+            .map_with_span(|val, span: Span| {
+                (Expr::Ret(Box::new(val)), span)
+            });
+
+        let delete_statement = just(Token::Delete)
+            .ignore_then(raw_expr.clone())
+            .then_ignore(just(Token::Ctrl(';')))
+            .map_with_span(|ptr, span: Span| {
+                (Expr::Delete(Box::new(ptr), None), span)
+            });
+
+        let break_statement = just(Token::Break)
+            .then_ignore(just(Token::Ctrl(';')))
+            .map_with_span(|_, span: Span| (Expr::Break, span));
+
+        let continue_statement = just(Token::Continue)
+            .then_ignore(just(Token::Ctrl(';')))
+            .map_with_span(|_, span: Span| (Expr::Continue, span));
+
+        // while loops, like if, have no parenthesis but always have a block
+        let while_ = just(Token::While)
+            .ignore_then(raw_expr.clone())
+            .then(block.clone())
+            .map_with_span(|(cond, body), span: Span| {
+                (Expr::While(Box::new(cond), Box::new(body)), span)
+            });
+
+        // for var i = 0 : i < length : i += 2
+        let standard_for = var_declaration
+            .then_ignore(just(Token::Ctrl(':')))
+            .then(raw_expr.clone())
+            .then_ignore(just(Token::Ctrl(':')))
+            .then(raw_expr.clone())
+            .map(|((init, cond), step)| {
+                ForHeader::Standard(Box::new(init), Box::new(cond), Box::new(step))
+            });
+
+        // for var i : 0..length, optionally with a type and a step, or for var item : collection
+        let ranged_for = just(Token::Var)
+            .ignore_then(ident)
+            .then(just(Token::As).ignore_then(type_parser().map_with_span(|typ, span: Span| (typ, span))).or_not())
+            .then_ignore(just(Token::Ctrl(':')))
+            .then(raw_expr.clone())
+            .then(
+                just(Token::Op("..".to_string()))
+                    .ignore_then(raw_expr.clone())
+                    .then(just(Token::Ctrl(':')).ignore_then(raw_expr.clone()).or_not())
+                    .or_not()
+            )
+            .map(|(((name, typ), start), range)| match range {
+                Some((end, step)) => ForHeader::Range(name, typ, Box::new(start), Box::new(end), step.map(Box::new)),
+                None => ForHeader::Each(name, Box::new(start)),
+            });
+
+        let for_ = just(Token::For)
+            .ignore_then(standard_for.or(ranged_for))
+            .then(block.clone())
+            .map_with_span(|(header, body), span: Span| {
+                (Expr::For(header, Box::new(body)), span)
+            });
+
+        // expressions are followed by a semicolon, unless they are block-like or the last in the block.
+        // the flag records whether the semicolon was there
+        let statement = var_statement
+            .or(ret_statement)
+            .or(delete_statement)
+            .or(break_statement)
+            .or(continue_statement)
+            .map(|statement| (statement, true))
+            .or(while_
+                .or(for_)
+                .then(just(Token::Ctrl(';')).or_not().map(|semicolon| semicolon.is_some())))
+            .or(raw_expr
+                .clone()
+                .then(just(Token::Ctrl(';')).or_not().map(|semicolon| semicolon.is_some())));
+
+        statement
+            .repeated()
+            .validate(|statements: Vec<(Spanned<Expr>, bool)>, _span, emit| {
+                for (statement, terminated) in statements.iter().rev().skip(1) {
+                    if !terminated && !is_block_like(&statement.0) {
+                        emit(Simple::custom(statement.1.end..statement.1.end, "expected ';' after expression"));
+                    }
+                }
+                statements
+            })
+            .map_with_span(|statements, span: Span| {
+                let produces_value = matches!(statements.last(), Some((_, false)));
+
+                let mut statements: Vec<Spanned<Expr>> = statements
+                    .into_iter()
+                    .map(|(statement, _terminated)| statement)
+                    .collect();
+
+                // a block that doesn't end in an expression has no value
+                if !produces_value {
+                    statements.push((Expr::Value(Value::Null), span.end..span.end));
+                }
+
+                statements
+                    .into_iter()
+                    .reduce(|a, b| {
+                        let span = a.1.start..b.1.end;
+                        (
+                            Expr::Then(
+                                Box::new(a),
+                                Box::new(b),
+                            ),
+                            span,
+                        )
+                    })
+                    .unwrap()
+            })
+    });
+
+    (single_expr, block_body)
+}
@@ -0,0 +1,70 @@
+use inkwell::module::Linkage;
+use inkwell::values::FunctionValue;
+
+use crate::ast::FunctionSignature;
+use crate::ast::Type;
+use crate::ast::method_symbol;
+use crate::compile::Compiler;
+
+
+/// Builds the signature of a function that comes with the language,
+/// which has no source for errors to point at.
+fn signature(params: Vec<(&str, Type)>, return_type: Type) -> FunctionSignature {
+    FunctionSignature {
+        return_type,
+        return_span: 0..0,
+        infers_return: false,
+        param_spans: vec![0..0; params.len()],
+        params: params.into_iter().map(|(name, typ)| (name.to_string(), typ)).collect(),
+        generic_params: Vec::new(),
+        generic_param_spans: Vec::new(),
+        span: 0..0,
+        owner: None,
+        is_static: false,
+    }
+}
+
+/// The functions and methods that every program can use without defining them, by the symbols they
+/// are known by in the module. print and println aren't among them, as they take any number of arguments,
+/// so the type checker turns calls of them into print expressions instead
+pub fn signatures() -> Vec<(String, FunctionSignature)> {
+    vec![
+        (method_symbol(&Type::Str, "len"), signature(vec![], Type::USize).into_method(Type::Str)),
+        ("exit".to_string(), signature(vec![("code", Type::I32)], Type::Void)),
+        ("abort".to_string(), signature(vec![], Type::Void)),
+    ]
+}
+
+impl<'a, 'ctx> Compiler<'a, 'ctx> {
+    /// Adds the functions that come with the language to the module. the ones that
+    /// libc already has are declared, and the compiler builds the others itself
+    pub fn define_runtime(&self) {
+        for (symbol, signature) in signatures() {
+            // the program may declare these itself, as externs of the same functions
+            if symbol == "exit" || symbol == "abort" {
+                if self.module.get_function(&symbol).is_none() {
+                    self.declare_extern(&symbol, &signature);
+                }
+                continue;
+            }
+
+            let function = self.declare_function(&symbol, &signature);
+            function.set_linkage(Linkage::Private);
+
+            let entry_point = self.context.append_basic_block(function, "entry");
+            self.builder.position_at_end(entry_point);
+
+            match symbol.as_str() {
+                "str.len" => self.build_str_len(function),
+                _ => unreachable!("every runtime function has a body"),
+            }
+        }
+    }
+
+    /// str: len() -> usize, the number of bytes in a string.
+    fn build_str_len(&self, function: FunctionValue<'ctx>) {
+        let this = function.get_nth_param(0).unwrap().into_pointer_value();
+        let len = self.builder.build_struct_gep(this, 1, "len").unwrap();
+        self.builder.build_return(Some(&self.builder.build_load(len, "len")));
+    }
+}
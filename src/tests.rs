@@ -0,0 +1,1579 @@
+use std::cell::Cell;
+
+use inkwell::context::Context;
+use inkwell::OptimizationLevel;
+
+use crate::ast::Error;
+use crate::apply_target_to_module;
+use crate::compile_program;
+use crate::create_compiler;
+use crate::get_native_target_machine;
+
+/// The stack the compiler is run on. the parser recurses deeply when it isn't optimised,
+/// which overflows the stacks that tests are run on by default
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Compiles a program into a module of its own, and hands the module to `with_module` if it compiled.
+/// otherwise the errors that stopped it from compiling are returned
+fn compile_with<T: Send + 'static>(
+    src: &str,
+    with_module: impl FnOnce(&inkwell::module::Module) -> T + Send + 'static,
+) -> Result<T, Vec<Error>> {
+    let src = src.to_string();
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || compile_on_this_thread(&src, with_module))
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+fn compile_on_this_thread<T>(src: &str, with_module: impl FnOnce(&inkwell::module::Module) -> T) -> Result<T, Vec<Error>> {
+    let context = Context::create();
+    let module = context.create_module("test");
+    let builder = context.create_builder();
+
+    let target_machine = get_native_target_machine();
+    apply_target_to_module(&target_machine, &module);
+
+    let compiler = create_compiler(&context, &builder, &module, &target_machine);
+    let (errs, semantic_errs) = compile_program(&compiler, src);
+
+    let errors: Vec<Error> = errs
+        .iter()
+        .map(|error| Error { span: error.span(), msg: error.to_string() })
+        .chain(semantic_errs)
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    module.verify().map_err(|error| vec![Error { span: 0..0, msg: error.to_string() }])?;
+    Ok(with_module(&module))
+}
+
+/// Compiles a program that is expected to compile, and returns its IR.
+fn compiles(src: &str) -> String {
+    match compile_with(src, |module| module.print_to_string().to_string()) {
+        Ok(ir) => ir,
+        Err(errors) => panic!("expected the program to compile, but got {:?}", errors),
+    }
+}
+
+thread_local! {
+    /// How many times the program being run on this thread has called tick().
+    static TICKS: Cell<i32> = Cell::new(0);
+}
+
+extern "C" fn tick() {
+    TICKS.with(|ticks| ticks.set(ticks.get() + 1));
+}
+
+extern "C" fn ticks() -> i32 {
+    TICKS.with(|ticks| ticks.get())
+}
+
+/// Compiles a program that is expected to compile, and runs its main function, which returns an i32.
+/// a program can count things by declaring and calling tick(), and read the count back with ticks()
+fn run(src: &str) -> i32 {
+    let result = compile_with(src, |module| {
+        let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+        for (name, address) in [("tick", tick as usize), ("ticks", ticks as usize)] {
+            if let Some(function) = module.get_function(name) {
+                engine.add_global_mapping(&function, address);
+            }
+        }
+
+        unsafe { engine.get_function::<unsafe extern "C" fn() -> i32>("main").unwrap().call() }
+    });
+
+    match result {
+        Ok(result) => result,
+        Err(errors) => panic!("expected the program to compile, but got {:?}", errors),
+    }
+}
+
+/// Compiles a program that is expected not to compile, and returns the errors that stopped it.
+fn errors(src: &str) -> Vec<Error> {
+    match compile_with(src, |_module| ()) {
+        Ok(()) => panic!("expected the program not to compile, but it did"),
+        Err(errors) => errors,
+    }
+}
+
+/// Compiles a program that is expected not to compile, and checks that one of its errors says what is expected.
+fn fails_with(src: &str, expected: &str) {
+    let errors = errors(src);
+    assert!(
+        errors.iter().any(|error| error.msg.contains(expected)),
+        "expected an error saying '{}', but got {:?}", expected, errors,
+    );
+}
+
+/// Checks that a program fails with an error saying what is expected, about the given part of the program.
+fn fails_at(src: &str, expected: &str, at: &str) {
+    let errors = errors(src);
+    assert!(
+        errors.iter().any(|error| error.msg.contains(expected) && &src[error.span.clone()] == at),
+        "expected an error saying '{}' at '{}', but got {:?}", expected, at,
+        errors.iter().map(|error| (&error.msg, &src[error.span.clone()])).collect::<Vec<_>>(),
+    );
+}
+
+// calls
+
+#[test]
+fn calls_a_function_defined_later() {
+    assert_eq!(run("
+        fn main() -> i32 { return add(2, 3); }
+        fn add(a as i32, b as i32) -> i32 { return a + b; }
+    "), 5);
+}
+
+#[test]
+fn rejects_a_call_with_the_wrong_number_of_arguments() {
+    fails_with("
+        fn add(a as i32, b as i32) -> i32 { return a + b; }
+        fn main() -> i32 { return add(2); }
+    ", "'add' called with wrong number of arguments (expected 2, found 1)");
+}
+
+#[test]
+fn calls_a_function_directly_by_its_symbol() {
+    let ir = compiles("
+        fn add(a as i32, b as i32) -> i32 { return a + b; }
+        fn main() -> i32 { return add(2, 3); }
+    ");
+    assert!(ir.contains("call i32 @add(i32 2, i32 3)"), "{}", ir);
+}
+
+// extern functions
+
+#[test]
+fn calls_a_c_function() {
+    assert_eq!(run("
+        extern fn abs(x as i32) -> i32;
+        fn main() -> i32 { return abs(0 - 4); }
+    "), 4);
+}
+
+#[test]
+fn rejects_an_extern_function_defined_twice() {
+    let src = "
+        extern fn abs(x as i32) -> i32;
+        fn abs(x as i32) -> i32 { return x; }
+        fn main() -> i32 { return abs(1); }
+    ";
+    fails_with(src, "abs is defined more than once");
+    fails_with(src, "abs is first defined here");
+}
+
+// type checking
+
+#[test]
+fn rejects_a_mismatched_return() {
+    fails_with("fn main() -> i32 { return true; }", "mismatched types: expected i32, found bool");
+}
+
+#[test]
+fn reports_an_unknown_type_where_it_is_written() {
+    fails_at("fn twice(x as Nowhere) -> i32 { return 0; } fn main() -> i32 { return 0; }", "unknown type Nowhere", "Nowhere");
+    fails_at("fn twice(x as i32) -> Nowhere { return 0; } fn main() -> i32 { return 0; }", "unknown type Nowhere", "Nowhere");
+}
+
+#[test]
+fn reports_an_unknown_variable_type_where_it_is_written() {
+    fails_at("fn main() -> i32 { var x as Nowhere = 0; return 0; }", "unknown type Nowhere", "Nowhere");
+    fails_at("fn main() -> i32 { for var i as Nowhere : 0..3 { } return 0; }", "unknown type Nowhere", "Nowhere");
+}
+
+#[test]
+fn converts_the_value_a_body_ends_in_to_the_return_type() {
+    assert_eq!(run("
+        fn wide() -> i64 { 5 }
+        fn main() -> i32 { if wide() == 5 { return 50; } return 0; }
+    "), 50);
+}
+
+// if and else
+
+#[test]
+fn gives_an_if_the_value_of_its_taken_branch() {
+    assert_eq!(run("fn main() -> i32 { var x as bool = true; return if x { 10 } else { 20 }; }"), 10);
+}
+
+#[test]
+fn gives_an_if_with_a_returning_branch_the_other_branch_s_type() {
+    let src = "
+        fn pick(c as bool) -> i32 {
+            var x as i32 = if c { return 1; } else { 2 };
+            return x + 40;
+        }
+        fn main() -> i32 { return pick(true) + pick(false); }
+    ";
+    assert_eq!(run(src), 43);
+}
+
+#[test]
+fn rejects_branches_of_different_types() {
+    fails_with("fn main() -> i32 { var x as i32 = if true { 1 } else { false }; return 0; }", "mismatched types: expected i32, found bool");
+}
+
+// loops
+
+#[test]
+fn counts_through_a_range() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            for var i : 2..5 { return i * 10; }
+            return 0;
+        }
+    "), 20);
+}
+
+#[test]
+fn continues_to_the_next_pass_of_a_range() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            for var i : 0..10 {
+                if i == 1 { continue; }
+                if i != 0 { return i; }
+            }
+            return 100;
+        }
+    "), 2);
+}
+
+#[test]
+fn breaks_out_of_the_innermost_loop_only() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            for var i : 0..3 {
+                while true { break; }
+                if i == 2 { return i * 10; }
+            }
+            return 100;
+        }
+    "), 20);
+}
+
+#[test]
+fn checks_the_condition_of_a_standard_for_loop_before_each_pass() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            for var i = 0 : false : i { return 100; }
+            for var j = 3 : j == 3 : j { return j * 2; }
+            return 0;
+        }
+    "), 6);
+}
+
+#[test]
+fn rejects_iterating_something_that_is_not_a_collection() {
+    fails_with("
+        fn main() -> i32 {
+            for var item : 5 { }
+            return 0;
+        }
+    ", "i32 is not a collection that can be iterated");
+}
+
+// comparison and logic
+
+#[test]
+fn orders_integers() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var x as i32 = 3;
+            if x > 2 && x <= 3 && -x < 0 { return 10; }
+            return 20;
+        }
+    "), 10);
+}
+
+#[test]
+fn short_circuits_logical_operators() {
+    assert_eq!(run("
+        extern fn abort() -> void;
+        fn never() -> bool { abort(); return true; }
+        fn main() -> i32 {
+            var a as bool = false && never();
+            var b as bool = true || never();
+            var c as bool = 1 < 2 && !(3 >= 4);
+            if c && !a && b { return 0; }
+            return 100;
+        }
+    "), 0);
+}
+
+#[test]
+fn rejects_ordering_bools() {
+    fails_with("fn main() -> i32 { var x as bool = true < false; return 0; }", "ordering is not defined for bool");
+}
+
+// assignment
+
+#[test]
+fn assigns_to_a_variable() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var x as i32 = 1;
+            x = x + 4;
+            x = x * x;
+            return x;
+        }
+    "), 25);
+}
+
+#[test]
+fn compound_assigns_to_a_variable() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var x as i32 = 5;
+            x += 10;
+            x *= 2;
+            x--;
+            return x;
+        }
+    "), 29);
+}
+
+#[test]
+fn adds_up_a_range() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var total as i32 = 0;
+            for var i : 0..5 { total += i; }
+            return total;
+        }
+    "), 10);
+}
+
+#[test]
+fn breaks_and_continues_a_while_loop() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var i as i32 = 0;
+            var total as i32 = 0;
+            while true {
+                i += 1;
+                if i > 10 { break; }
+                if i == 3 { continue; }
+                total += i;
+            }
+            return total * 100 + i;
+        }
+    "), 5211);
+}
+
+#[test]
+fn steps_a_standard_for_loop_even_when_it_continues() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var total as i32 = 0;
+            for var i as i32 = 0 : i < 10 : i += 2 {
+                if i == 4 { continue; }
+                if i == 8 { break; }
+                total += i;
+            }
+            var passes as i32 = 0;
+            for var j as i32 = 10 : j > 0 : j -= 3 { passes += 1; }
+            return total * 10 + passes;
+        }
+    "), 84);
+}
+
+#[test]
+fn steps_through_a_range() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var passes as i32 = 0;
+            var total as i32 = 0;
+            for var i : 0..10 : i += 3 {
+                passes += 1;
+                total += i;
+            }
+            return passes * 100 + total;
+        }
+    "), 418);
+}
+
+#[test]
+fn rejects_a_range_step_that_does_not_assign_to_its_variable() {
+    fails_at("
+        fn main() -> i32 {
+            for var i : 0..10 : 2 { return i; }
+            return 0;
+        }
+    ", "the step of a range has to assign to i, as in i += 2", "2");
+}
+
+#[test]
+fn rejects_assigning_to_a_call() {
+    fails_with("
+        fn five() -> i32 { return 5; }
+        fn main() -> i32 { five() = 3; return 0; }
+    ", "cannot assign to this expression");
+}
+
+// number literals
+
+#[test]
+fn infers_the_type_of_a_variable_from_its_literal() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var x = 3;
+            var y = if x > 2 { 10 } else { 20 };
+            var total = 0;
+            for var i : 0..5 { total += i; }
+            return y + total;
+        }
+    "), 20);
+}
+
+#[test]
+fn reads_hex_binary_and_suffixed_literals() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var a = 0xff;
+            var b = 0b101;
+            var c = 7i64;
+            var d = 2.5;
+            if d > 2.0 && c == 7 { return a + b; }
+            return 0;
+        }
+    "), 260);
+}
+
+#[test]
+fn rejects_a_literal_out_of_range() {
+    fails_with("fn main() -> i32 { var x as i32 = 3000000000; return 0; }", "literal out of range for i32");
+}
+
+#[test]
+fn rejects_a_literal_out_of_range_of_an_unsigned_type() {
+    fails_with("fn main() -> i32 { var x as u8 = 256; return 0; }", "literal out of range for u8");
+}
+
+// primitive types
+
+#[test]
+fn lays_out_every_primitive_type() {
+    let ir = compiles("
+        fn letter(k as char) -> i32 { return 0; }
+        fn main() -> i32 {
+            var a as i8 = 1; var b as i16 = 2; var c as i64 = 3;
+            var d as u8 = 4; var e as u16 = 5; var f as u32 = 6; var g as u64 = 7;
+            var h as f32 = 1.5; var i as f64 = 2.5; var j as usize = 8;
+            return 0;
+        }
+    ");
+    // unsigned integers and chars share the layout of the signed integers, and usize is as wide as a pointer
+    let usize_type = format!("i{}", std::mem::size_of::<usize>() * 8);
+    let layouts = [
+        ("a", "i8"), ("b", "i16"), ("c", "i64"),
+        ("d", "i8"), ("e", "i16"), ("f", "i32"), ("g", "i64"),
+        ("h", "float"), ("i", "double"), ("j", usize_type.as_str()), ("k", "i8"),
+    ];
+    for (name, typ) in layouts {
+        assert!(ir.contains(&format!("%{} = alloca {},", name, typ)), "{}", ir);
+    }
+}
+
+#[test]
+fn rejects_mixing_integer_types() {
+    fails_with("
+        fn main() -> i32 {
+            var a as i8 = 1;
+            var b as i64 = 2;
+            var c = a + b;
+            return 0;
+        }
+    ", "mismatched types: expected i8, found i64");
+}
+
+// casts
+
+#[test]
+fn casts_between_numbers() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var big = 300;
+            var small = big as u8;
+            var half = (7 as f64) / 2.0;
+            return small as i32 + (half * 10.0) as i32;
+        }
+    "), 44 + 35);
+}
+
+#[test]
+fn casts_between_chars_and_integers() {
+    assert_eq!(run("
+        fn wide() -> i64 { 5 }
+        fn main() -> i32 {
+            var k = 107 as char;
+            return k as i32 + wide() as i32 * 1000;
+        }
+    "), 5107);
+}
+
+#[test]
+fn casts_a_value_to_its_own_type() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var x = 7;
+            var y = x as i32;
+            var b = true as bool;
+            if b { return y; }
+            return 0;
+        }
+    "), 7);
+}
+
+#[test]
+fn rejects_casting_to_or_from_void() {
+    let src = "
+        fn nothing() -> void { }
+        fn main() -> i32 { var x = nothing() as i32; return 0; }
+    ";
+    fails_at(src, "this has no value, so cannot be cast to i32", "nothing()");
+    fails_with("fn main() -> i32 { 5 as void; return 0; }", "cannot cast to void, as it has no values");
+}
+
+#[test]
+fn rejects_casting_a_bool_from_a_number() {
+    fails_with("fn main() -> i32 { var b = 1 as bool; return 0; }", "cannot cast i32 to bool");
+}
+
+// classes
+
+#[test]
+fn reads_and_writes_fields() {
+    assert_eq!(run("
+        class Point { x as i32; y as i32 = 3; }
+        fn main() -> i32 {
+            var p as Point;
+            p.x = 4;
+            return p.x * 10 + p.y;
+        }
+    "), 43);
+}
+
+#[test]
+fn rejects_a_class_defined_twice() {
+    let src = "
+        class Point { x as i32; }
+        class Point { y as i32; }
+        fn main() -> i32 { return 0; }
+    ";
+    fails_at(src, "Point is defined more than once", "class Point { y as i32; }");
+    fails_at(src, "Point is first defined here", "class Point { x as i32; }");
+}
+
+// methods
+
+#[test]
+fn calls_methods_with_an_implicit_this() {
+    assert_eq!(run("
+        class Counter {
+            count as i32;
+            fn bump(by as i32) -> i32 { this.count += by; count++; return count; }
+        }
+        fn main() -> i32 {
+            var c as Counter;
+            c.bump(2);
+            return c.bump(3);
+        }
+    "), 7);
+}
+
+#[test]
+fn calls_a_method_before_a_function_of_the_same_name() {
+    assert_eq!(run("
+        fn twice(x as i32) -> i32 { return x * 2; }
+        class Counter {
+            count as i32;
+            fn twice(x as i32) -> i32 { return x * 3; }
+            fn step() -> i32 { return twice(2); }
+        }
+        fn main() -> i32 {
+            var c as Counter;
+            return c.step() * 10 + twice(1);
+        }
+    "), 62);
+}
+
+#[test]
+fn rejects_a_method_that_does_not_exist() {
+    fails_with("
+        class Counter { count as i32; }
+        fn main() -> i32 { var c as Counter; return c.reset(); }
+    ", "Counter has no method named reset");
+}
+
+// destructors
+
+/// A class that counts how many times it has been destroyed, with tick().
+const TRACKED: &str = "
+    extern fn tick() -> void;
+    extern fn ticks() -> i32;
+    class Tracked {
+        : delete() { tick(); }
+        fn touch() -> i32 { return 1; }
+    }
+    fn take(tracked as Tracked) -> i32 { return 0; }
+";
+
+#[test]
+fn destroys_temporaries_at_the_end_of_their_statement() {
+    assert_eq!(run(&format!("{}
+        fn count() -> i32 {{
+            Tracked().touch();
+            var after_method = ticks();
+            take(Tracked());
+            var after_argument = ticks();
+            take(if true {{ Tracked() }} else {{ Tracked() }});
+            return after_method * 100 + after_argument * 10 + ticks();
+        }}
+        fn main() -> i32 {{ return count(); }}
+    ", TRACKED)), 123);
+}
+
+#[test]
+fn destroys_an_object_that_is_assigned_over() {
+    assert_eq!(run(&format!("{}
+        fn count() -> i32 {{
+            var tracked = Tracked();
+            tracked = Tracked();
+            return ticks();
+        }}
+        fn main() -> i32 {{ var during = count(); return during * 10 + ticks(); }}
+    ", TRACKED)), 12);
+}
+
+#[test]
+fn destroys_variables_in_reverse_declaration_order() {
+    // each destructor only ticks if the other has already run, so the count is 3 only when second goes first
+    assert_eq!(run("
+        extern fn tick() -> void;
+        extern fn ticks() -> i32;
+        class First {
+            : delete() { if ticks() == 1 { tick(); tick(); } }
+        }
+        class Second {
+            : delete() { if ticks() == 0 { tick(); } }
+        }
+        fn scope() -> void {
+            var first = First();
+            var second = Second();
+        }
+        fn main() -> i32 { scope(); return ticks(); }
+    "), 3);
+}
+
+#[test]
+fn moves_an_object_out_of_a_variable_it_is_copied_from() {
+    assert_eq!(run(&format!("{}
+        fn count() -> i32 {{
+            var a = Tracked();
+            var b = a;
+            a = b;
+            a = a;
+            var i = 0;
+            while i < 3 {{
+                var c = Tracked();
+                var d = c;
+                i += 1;
+            }}
+            var e = Tracked();
+            if ticks() > 100 {{
+                var f = e;
+            }}
+            return ticks();
+        }}
+        fn main() -> i32 {{ var during = count(); return during * 10 + ticks(); }}
+    ", TRACKED)), 35);
+}
+
+#[test]
+fn rejects_copying_an_object_that_is_not_in_a_variable() {
+    fails_at(&format!("{}
+        fn keep(tracked as Tracked) -> i32 {{ var copy = tracked; return 0; }}
+        fn main() -> i32 {{ return 0; }}
+    ", TRACKED), "Tracked needs destroying, so can only be moved out of a variable, as this copy of it would be destroyed twice", "tracked");
+}
+
+#[test]
+fn moves_a_returned_object_out_of_its_function() {
+    assert_eq!(run(&format!("{}
+        fn make() -> Tracked {{ var tracked = Tracked(); return tracked; }}
+        fn make_last() -> Tracked {{ var tracked = Tracked(); tracked }}
+        fn main() -> i32 {{
+            var a = make();
+            var b = make_last();
+            var kept = ticks();
+            make();
+            return kept * 10 + ticks();
+        }}
+    ", TRACKED)), 1);
+}
+
+// inheritance
+
+#[test]
+fn calls_the_override_through_a_base_pointer() {
+    assert_eq!(run("
+        class Shape {
+            sides as i32 = 0;
+            fn area() -> i32 { return 0; }
+            fn measure() -> i32 { return area() * 10 + sides; }
+        }
+        class Square -> Shape {
+            width as i32;
+            : new(width as i32) { this.width = width; sides = 4; }
+            fn area() -> i32 { return width * width; }
+        }
+        fn main() -> i32 {
+            var square = Square(3);
+            var base as Shape;
+            return square.measure() * 100 + base.measure();
+        }
+    "), 9400);
+}
+
+#[test]
+fn destroys_the_base_part_of_a_derived_object() {
+    assert_eq!(run("
+        extern fn tick() -> void;
+        extern fn ticks() -> i32;
+        class Base {
+            : delete() { tick(); }
+        }
+        class Derived -> Base {
+            : delete() { for var i : 0..10 { tick(); } }
+        }
+        fn destroy() -> void { var object as Derived; }
+        fn main() -> i32 { destroy(); return ticks(); }
+    "), 11);
+}
+
+#[test]
+fn rejects_an_override_with_a_different_signature() {
+    fails_with("
+        class Shape { fn area() -> i32 { return 0; } }
+        class Square -> Shape { fn area() -> f64 { return 1.0; } }
+        fn main() -> i32 { return 0; }
+    ", "overrides Shape.area, so must have the same parameters and return type");
+}
+
+#[test]
+fn rejects_a_base_object_used_as_a_derived_one() {
+    fails_with("
+        class Shape { sides as i32; }
+        class Square -> Shape { width as i32; }
+        fn main() -> i32 {
+            var shape as Shape;
+            var square as Square = shape;
+            return 0;
+        }
+    ", "mismatched types: expected Square, found Shape");
+}
+
+// out-of-line and extension methods
+
+#[test]
+fn calls_methods_defined_outside_their_class() {
+    assert_eq!(run("
+        class Counter { count as i32; }
+        fn Counter: bump(by as i32) -> i32 { count += by; return count; }
+        Counter: new(start as i32) { count = start; }
+        fn i32: add(other as i32) -> i32 { return *this + other; }
+        fn main() -> i32 {
+            var counter = Counter(4);
+            counter.bump(1);
+            return 5.add(3) * 100 + counter.count * counter.count;
+        }
+    "), 825);
+}
+
+#[test]
+fn calls_a_static_extension_method_on_its_type() {
+    assert_eq!(run("
+        fn i32: square(x as i32) static -> i32 { return x * x; }
+        fn main() -> i32 { return i32.square(7); }
+    "), 49);
+}
+
+#[test]
+fn rejects_calling_a_static_extension_on_a_value() {
+    fails_with("
+        fn i32: square(x as i32) static -> i32 { return x * x; }
+        fn main() -> i32 { return 5.square(2); }
+    ", "square is static, so has to be called as i32.square()");
+}
+
+#[test]
+fn rejects_an_instance_field_used_in_a_static_method() {
+    fails_at("
+        class Counter {
+            count as i32;
+            fn reset() static -> void { count = 0; }
+        }
+        fn main() -> i32 { return 0; }
+    ", "count is not static, so cannot be used in a static method", "count");
+}
+
+// static fields
+
+#[test]
+fn initialises_static_fields_with_negated_literals() {
+    assert_eq!(run("
+        class Limits {
+            low as static i32 = -5;
+            high as static i32 = - -7;
+            scale as static f64 = -(-(-2.5));
+        }
+        fn main() -> i32 { return Limits.low + Limits.high * 10 + (Limits.scale * 2.0) as i32; }
+    "), -5 + 70 - 5);
+}
+
+#[test]
+fn rejects_a_static_field_initialised_with_an_expression() {
+    fails_with("
+        class Limits { low as static i32 = 2 + 3; }
+        fn main() -> i32 { return Limits.low; }
+    ", "static fields can only be initialised with a literal");
+}
+
+// pointers and the heap
+
+#[test]
+fn writes_through_a_pointer_to_a_local() {
+    assert_eq!(run("
+        fn set(target as i32*, value as i32) -> void { *target = value; }
+        fn main() -> i32 {
+            var x as i32 = 1;
+            set(&x, 42);
+            return x;
+        }
+    "), 42);
+}
+
+#[test]
+fn builds_a_linked_list_on_the_heap() {
+    assert_eq!(run("
+        class Node {
+            value as i32;
+            next as Node*;
+            : new(value as i32, next as Node*) { this.value = value; this.next = next; }
+        }
+        fn main() -> i32 {
+            var head = new Node(1, new Node(2, new Node(3, null)));
+            var total as i32 = 0;
+            var node = head;
+            while node != null {
+                total = total * 10 + node.value;
+                var next = node.next;
+                delete node;
+                node = next;
+            }
+            return total;
+        }
+    "), 123);
+}
+
+#[test]
+fn deletes_a_derived_object_through_a_base_pointer() {
+    assert_eq!(run("
+        extern fn tick() -> void;
+        extern fn ticks() -> i32;
+        class Base {
+            fn size() -> i32 { return 1; }
+            : delete() { tick(); }
+        }
+        class Derived -> Base {
+            : delete() { for var i : 0..10 { tick(); } }
+        }
+        fn main() -> i32 {
+            var object as Base* = new Derived();
+            delete object;
+            return ticks();
+        }
+    "), 11);
+}
+
+#[test]
+fn rejects_deleting_memory_not_made_by_new() {
+    fails_with("
+        class Node { value as i32; }
+        fn main() -> i32 {
+            var node as Node;
+            delete &node;
+            return 0;
+        }
+    ", "wasn't made by new, so cannot be deleted");
+}
+
+#[test]
+fn rejects_new_on_a_function_call() {
+    fails_with("
+        fn add(a as i32, b as i32) -> i32 { return a + b; }
+        fn main() -> i32 {
+            var x = new add(1, 2);
+            return 0;
+        }
+    ", "only instances of classes can be created with new");
+}
+
+// strings
+
+#[test]
+fn reads_the_length_and_bytes_of_a_string() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var greeting as str = \"hello\";
+            return greeting.len() as i32 * 1000 + greeting[1] as i32;
+        }
+    "), 5000 + 'e' as i32);
+}
+
+#[test]
+fn lexes_escape_sequences_in_strings() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var text = \"a\\n\\\"\\u{e9}\";
+            return text.len() as i32 * 1000 + text[1] as i32 * 10 + text[2] as i32;
+        }
+    "), 5000 + 100 + '"' as i32);
+}
+
+#[test]
+fn counts_characters_in_a_loop() {
+    assert_eq!(run("
+        fn count(text as str, wanted as char) -> i32 {
+            var found = 0;
+            for var i : 0..text.len() {
+                if text[i] == wanted { found += 1; }
+            }
+            return found;
+        }
+        fn main() -> i32 { return count(\"banana\", 97 as char); }
+    "), 3);
+}
+
+#[test]
+fn rejects_indexing_a_string_with_a_signed_integer() {
+    fails_with("
+        fn main() -> i32 {
+            var index as i32 = 0;
+            return \"abc\"[index] as i32;
+        }
+    ", "mismatched types: expected usize, found i32");
+}
+
+// printing
+
+#[test]
+fn builds_a_printf_format_from_a_format_string() {
+    let ir = compiles("
+        fn main() -> i32 {
+            var name = \"World\";
+            println(\"hello, {0}! {1} is {}% {2}\", name, 100u8, true);
+            print(2.5);
+            return 0;
+        }
+    ");
+    assert!(ir.contains("c\"hello, %.*s! %llu is %.*s%% %s\\0A\\00\""), "{}", ir);
+    assert!(ir.contains("c\"%g\\00\""), "{}", ir);
+}
+
+#[test]
+fn prints_and_exits() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            println(\"{} and {} make {{{}}}\", 2, -3, 2 + -3);
+            println();
+            return 7;
+        }
+    "), 7);
+}
+
+#[test]
+fn lets_a_program_declare_exit_as_an_extern_of_its_own() {
+    assert_eq!(run("
+        extern fn exit(returnValue as i32) -> void;
+        fn main() -> i32 {
+            if false { exit(1); }
+            return 3;
+        }
+    "), 3);
+}
+
+#[test]
+fn rejects_defining_a_function_the_prelude_reserves() {
+    let src = "
+        fn abort() -> void { }
+        fn main() -> i32 { return 0; }
+    ";
+    fails_at(src, "abort is reserved by the prelude, so cannot be defined again", "fn abort() -> void");
+    // the prelude has no source, so nothing points into the program as if it did
+    assert!(errors(src).iter().all(|error| error.span != (0..0)));
+}
+
+#[test]
+fn rejects_a_format_string_without_enough_arguments() {
+    fails_with("
+        fn main() -> i32 { println(\"{0} and {1}\", 5); return 0; }
+    ", "there is no argument 1 to print, as only 1 were given");
+}
+
+#[test]
+fn rejects_an_argument_that_is_never_printed() {
+    fails_at("
+        fn main() -> i32 { println(\"{0}\", 5, 6); return 0; }
+    ", "this is never printed, as the format string has no {1}", "6");
+}
+
+#[test]
+fn rejects_printing_an_object() {
+    fails_with("
+        class Point { x as i32; }
+        fn main() -> i32 { var point as Point; println(\"{}\", point); return 0; }
+    ", "Point cannot be printed");
+}
+
+// generic functions
+
+#[test]
+fn instantiates_generic_functions_for_each_type() {
+    assert_eq!(run("
+        fn max<T>(a as T, b as T) -> T {
+            if a > b { return a; }
+            return b;
+        }
+        fn main() -> i32 {
+            var big = max(1.5, 2);
+            return max(3, 7) * 10 + big as i32 + max<u8>(200, 100) as i32;
+        }
+    "), 70 + 2 + 200);
+}
+
+#[test]
+fn infers_type_arguments_through_pointers() {
+    assert_eq!(run("
+        fn swap<T>(a as T*, b as T*) -> void {
+            var held as T = *a;
+            *a = *b;
+            *b = held;
+        }
+        fn main() -> i32 {
+            var x = 1;
+            var y = 2;
+            swap(&x, &y);
+            return x * 10 + y;
+        }
+    "), 21);
+}
+
+#[test]
+fn compiles_one_instance_per_set_of_type_arguments() {
+    let ir = compiles("
+        fn first<T, U>(a as T, b as U) -> T { return a; }
+        fn main() -> i32 {
+            var a = first(1, true);
+            var b = first(2, false);
+            var c = first<i64, bool>(3, true);
+            return a + b;
+        }
+    ");
+    assert_eq!(ir.matches("define i32 @\"first<i32, bool>\"").count(), 1, "{}", ir);
+    assert!(ir.contains("define i64 @\"first<i64, bool>\""), "{}", ir);
+}
+
+#[test]
+fn rejects_a_type_parameter_that_cannot_be_inferred() {
+    fails_with("
+        fn make<T>() -> T { var made as T; return made; }
+        fn main() -> i32 { return make(); }
+    ", "cannot work out what T is for this call of 'make'");
+}
+
+#[test]
+fn rejects_duplicate_type_parameters() {
+    fails_at("
+        fn pick<T, T>(a as T) -> T { return a; }
+        fn main() -> i32 { return 0; }
+    ", "the type parameter T is declared more than once", "T");
+}
+
+// generic classes
+
+#[test]
+fn stores_a_value_of_each_type_in_a_generic_class() {
+    assert_eq!(run("
+        class Box<T> {
+            value as T;
+            fn get() -> T { return value; }
+            fn set(value as T) -> void { this.value = value; }
+        }
+        fn main() -> i32 {
+            var small as Box<u8>;
+            small.set(200);
+            var wide = Box<f64>();
+            wide.set(2.5);
+            return small.get() as i32 + (wide.get() * 2.0) as i32;
+        }
+    "), 205);
+}
+
+#[test]
+fn builds_a_generic_stack_on_the_heap() {
+    assert_eq!(run("
+        class Node<T> {
+            value as T;
+            next as Node<T>*;
+            : new(value as T, next as Node<T>*) { this.value = value; this.next = next; }
+        }
+        class Stack<T> {
+            top as Node<T>*;
+            fn push(value as T) -> void { top = new Node<T>(value, top); }
+            fn pop() -> T {
+                var node = top;
+                var value = node.value;
+                top = node.next;
+                delete node;
+                return value;
+            }
+        }
+        fn main() -> i32 {
+            var stack as Stack<i32>;
+            stack.push(1);
+            stack.push(2);
+            stack.push(3);
+            return stack.pop() * 100 + stack.pop() * 10 + stack.pop();
+        }
+    "), 321);
+}
+
+#[test]
+fn infers_type_arguments_through_a_generic_class() {
+    assert_eq!(run("
+        class Pair<A, B> { first as A; second as B; }
+        fn swapped<A, B>(pair as Pair<A, B>*) -> Pair<B, A> {
+            var result as Pair<B, A>;
+            result.first = pair.second;
+            result.second = pair.first;
+            return result;
+        }
+        fn main() -> i32 {
+            var pair as Pair<i32, bool>;
+            pair.first = 7;
+            pair.second = true;
+            var result = swapped(&pair);
+            if result.first { return result.second; }
+            return 0;
+        }
+    "), 7);
+}
+
+#[test]
+fn instantiates_only_the_methods_that_are_called() {
+    let ir = compiles("
+        class Box<T> {
+            value as T;
+            fn get() -> T { return value; }
+            fn doubled() -> T { return value * 2; }
+        }
+        fn main() -> i32 {
+            var flag as Box<bool>;
+            var number as Box<i64>;
+            if flag.get() { return 1; }
+            return number.doubled() as i32;
+        }
+    ");
+    assert!(ir.contains("%\"Box<bool>\" = type { i1 }"), "{}", ir);
+    assert!(ir.contains("%\"Box<i64>\" = type { i64 }"), "{}", ir);
+    assert!(ir.contains("define i64 @\"Box<i64>.doubled\""), "{}", ir);
+    assert!(!ir.contains("Box<bool>.doubled"), "{}", ir);
+    assert!(!ir.contains("%Box = type"), "{}", ir);
+}
+
+#[test]
+fn rejects_a_generic_class_given_the_wrong_number_of_type_arguments() {
+    fails_with("
+        class Pair<A, B> { first as A; second as B; }
+        fn main() -> i32 { var pair as Pair<i32>; return 0; }
+    ", "Pair takes 2 type arguments, but is given 1 in Pair<i32>");
+}
+
+#[test]
+fn rejects_a_generic_function_that_gives_a_class_the_wrong_number_of_type_arguments() {
+    fails_with("
+        class Pair<A, B> { first as A; second as B; }
+        fn first_of<A>(pair as Pair<A>*) -> A { return pair.first; }
+        fn main() -> i32 {
+            var pair as Pair<i32, i32>;
+            return first_of<i32>(&pair);
+        }
+    ", "Pair takes 2 type arguments, but is given 1 in Pair<i32>");
+}
+
+// tagged unions
+
+#[test]
+fn builds_variants_and_reads_their_tags() {
+    assert_eq!(run("
+        union Shape { Circle(f64); Rect(f64, f64); Empty; }
+        fn kind(shape as Shape) -> i32 {
+            if shape is Circle { return 1; }
+            if shape is Rect { return 2; }
+            return 3;
+        }
+        fn main() -> i32 {
+            var a = Shape.Circle(1.5);
+            var b = Shape.Rect(2.0, 3);
+            var c as Shape = Shape.Empty;
+            return kind(a) * 100 + kind(b) * 10 + kind(c);
+        }
+    "), 123);
+}
+
+#[test]
+fn sizes_a_union_for_its_largest_variant() {
+    let ir = compiles("
+        union Number { Small(u8); Big(i64, i32); Nothing; }
+        union Wrapper { Wrapped(Number); }
+        fn main() -> i32 {
+            var wrapper = Wrapper.Wrapped(Number.Big(1, 2));
+            if wrapper is Wrapped { return 1; }
+            return 0;
+        }
+    ");
+    assert!(ir.contains("%Number = type { i32, [0 x { i64, i32 }], [16 x i8] }"), "{}", ir);
+    assert!(ir.contains("%Wrapper = type { i32, [0 x { %Number }], [24 x i8] }"), "{}", ir);
+}
+
+#[test]
+fn rejects_a_variant_that_does_not_exist() {
+    fails_with("
+        union Shape { Circle(f64); Empty; }
+        fn main() -> i32 {
+            var shape = Shape.Circle(1.0);
+            if shape is Square { return 1; }
+            return 0;
+        }
+    ", "Shape has no variant named Square");
+}
+
+#[test]
+fn rejects_a_variant_given_the_wrong_number_of_values() {
+    fails_with("
+        union Shape { Rect(f64, f64); Empty; }
+        fn main() -> i32 { var shape = Shape.Rect(1.0); return 0; }
+    ", "'Shape.Rect' called with wrong number of arguments (expected 2, found 1)");
+}
+
+#[test]
+fn rejects_a_union_without_a_value() {
+    fails_with("
+        union Shape { Circle(f64); Empty; }
+        fn main() -> i32 { var shape as Shape; return 0; }
+    ", "Shape is a union, so has no default value and has to be given one");
+}
+
+// pattern matching
+
+#[test]
+fn matches_literals_alternatives_and_names() {
+    assert_eq!(run("
+        fn classify(x as i32) -> i32 {
+            return match x {
+                0 => 10;
+                1, 2 => 20;
+                -5 => 30;
+                n => n * 2;
+            };
+        }
+        fn truth(b as bool) -> i32 {
+            match b {
+                true => 1;
+                false => 0;
+            }
+        }
+        fn main() -> i32 {
+            return classify(0) + classify(2) + classify(-5) + classify(7) + truth(true) * 100;
+        }
+    "), 174);
+}
+
+#[test]
+fn binds_the_values_that_variants_hold() {
+    assert_eq!(run("
+        union Shape { Circle(i32); Rect(i32, i32); Empty; }
+        union Slot { Full(Shape); Vacant; }
+        fn area(slot as Slot) -> i32 {
+            return match slot {
+                Full(Rect(w, 0)) => 0 - 1;
+                Full(Rect(w, h)) => w * h;
+                Full(Circle(r)) => {
+                    var squared = r * r;
+                    3 * squared
+                }
+                Full(Empty), Vacant => 0;
+            };
+        }
+        fn main() -> i32 {
+            return area(Slot.Full(Shape.Rect(2, 3))) + area(Slot.Full(Shape.Circle(1))) * 10
+                + area(Slot.Vacant) + area(Slot.Full(Shape.Rect(5, 0)));
+        }
+    "), 35);
+}
+
+#[test]
+fn switches_on_the_tag_when_only_the_variant_is_tested() {
+    let ir = compiles("
+        union Shape { Circle(f64); Rect(f64, f64); Empty; }
+        fn area(shape as Shape) -> f64 {
+            match shape {
+                Circle(r) => 3.0 * r * r;
+                Rect(w, h) => w * h;
+                Empty => 0.0;
+            }
+        }
+        fn main() -> i32 { return area(Shape.Rect(2.0, 3.0)) as i32; }
+    ");
+    assert!(ir.contains("switch i32 %tag"), "{}", ir);
+}
+
+#[test]
+fn rejects_a_match_that_misses_a_case() {
+    let src = "
+        union Shape { Circle(f64); Rect(f64, f64); Empty; }
+        fn main() -> i32 {
+            var shape = Shape.Circle(1.0);
+            match shape {
+                Circle(_) => { return 1; }
+            }
+            return 0;
+        }
+    ";
+    fails_at(src, "the arms of the match don't cover every Shape, such as Rect(_, _), Empty", "shape");
+
+    // the missing arms are pointed out at the closing brace of the match, where they would go
+    let brace = src.find("}\n            return 0;").unwrap();
+    let errors = errors(src);
+    assert!(
+        errors.iter().any(|error| error.msg == "the arms that cover Rect(_, _), Empty would go here" && error.span == (brace..brace + 1)),
+        "{:?}", errors,
+    );
+}
+
+#[test]
+fn rejects_an_unreachable_pattern() {
+    fails_at("
+        fn main() -> i32 {
+            var x = 3;
+            return match x {
+                1, 2 => 1;
+                _ => 2;
+                3 => 3;
+            };
+        }
+    ", "this pattern is unreachable", "3");
+}
+
+// closures
+
+#[test]
+fn calls_functions_through_function_values() {
+    assert_eq!(run("
+        fn add(a as i32, b as i32) -> i32 { return a + b; }
+        fn mul(a as i32, b as i32) -> i32 { return a * b; }
+        fn apply(op as fn(i32, i32) -> i32, a as i32, b as i32) -> i32 { return op(a, b); }
+        fn main() -> i32 {
+            var f = add;
+            var result = apply(f, 2, 3) * 10;
+            f = mul;
+            return result + f(2, 3);
+        }
+    "), 56);
+}
+
+#[test]
+fn captures_by_value_and_by_reference() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var total = 0;
+            var step = 2;
+            var add = fn[&total, step](times as i32) {
+                for var i : 0..times { total += step; }
+            };
+            step = 100;
+            add(3);
+            add(2);
+            return total;
+        }
+    "), 10);
+}
+
+#[test]
+fn stores_a_closure_in_a_field_and_calls_it_like_a_method() {
+    assert_eq!(run("
+        class Button {
+            on_press as fn(i32) -> i32 = fn(count as i32) -> i32 { count };
+        }
+        fn main() -> i32 {
+            var button as Button;
+            var bonus = 5;
+            var first = button.on_press(1);
+            button.on_press = fn[bonus](count as i32) -> i32 { count * 10 + bonus };
+            return first + button.on_press(2);
+        }
+    "), 26);
+}
+
+#[test]
+fn makes_a_closure_on_every_pass_of_a_loop() {
+    assert_eq!(run("
+        fn main() -> i32 {
+            var total = 0;
+            for var i : 0..1000 {
+                var twice = fn[i]() -> i32 { i * 2 };
+                total += twice();
+            }
+            return total;
+        }
+    "), 999000);
+}
+
+#[test]
+fn rejects_returning_a_closure_that_captures() {
+    fails_at("
+        fn counter(start as i32) -> fn() -> i32 {
+            var next = fn[start]() -> i32 { start + 1 };
+            return next;
+        }
+        fn main() -> i32 { return 0; }
+    ", "a closure that captures variables only lives as long as the call that made it, so cannot be returned", "next");
+}
+
+#[test]
+fn rejects_storing_a_closure_that_captures_through_a_pointer() {
+    fails_with("
+        class Button {
+            on_press as fn(i32) -> i32 = fn(count as i32) -> i32 { count };
+        }
+        fn attach(button as Button*, bonus as i32) -> void {
+            button.on_press = fn[bonus](count as i32) -> i32 { count + bonus };
+        }
+        fn main() -> i32 {
+            var button as Button;
+            attach(&button, 5);
+            return button.on_press(1);
+        }
+    ", "a closure that captures variables only lives as long as the call that made it, so can only be stored in its variables");
+}
+
+#[test]
+fn rejects_a_variable_that_is_not_captured() {
+    fails_at("
+        fn main() -> i32 {
+            var count = 1;
+            var f = fn() -> i32 { count };
+            return f();
+        }
+    ", "count belongs to the enclosing function, so has to be captured", "count");
+}
+
+#[test]
+fn rejects_a_function_value_called_with_the_wrong_arguments() {
+    fails_with("
+        fn main() -> i32 {
+            var f = fn(a as i32, b as i32) -> i32 { a + b };
+            return f(1);
+        }
+    ", "'f' called with wrong number of arguments (expected 2, found 1)");
+}
+
+// return type inference
+
+#[test]
+fn infers_the_return_type_from_the_returns() {
+    assert_eq!(run("
+        fn set(target as i32*, value as i32) { *target = value; }
+        fn half(x as i32) {
+            if x < 0 { return 0; }
+            return x / 2;
+        }
+        fn main() {
+            var n = 0;
+            set(&n, 7);
+            return n + half(10) + half(-3);
+        }
+    "), 12);
+}
+
+#[test]
+fn infers_the_return_types_of_functions_that_call_each_other() {
+    assert_eq!(run("
+        fn main() { return count_down(5) + factorial(4); }
+        fn count_down(n as i32) {
+            if n == 0 { return 0; }
+            return step(n) + 1;
+        }
+        fn step(n as i32) { return count_down(n - 1); }
+        fn factorial(n as i32) {
+            if n <= 1 { return 1; }
+            return n * factorial(n - 1);
+        }
+    "), 29);
+}
+
+#[test]
+fn lets_a_literal_return_take_the_type_of_a_later_one() {
+    assert_eq!(run("
+        fn clamp(x as i64) {
+            if x < 0 { return 0; }
+            return x;
+        }
+        fn main() -> i32 { return clamp(-5i64) as i32 + clamp(3i64) as i32; }
+    "), 3);
+}
+
+#[test]
+fn returns_the_value_the_body_ends_in() {
+    assert_eq!(run("
+        fn double(x as i32) { x * 2 }
+        fn main() { double(4) + 1 }
+    "), 9);
+}
+
+#[test]
+fn rejects_returns_of_different_types() {
+    let src = "
+        fn pick(x as i32) {
+            if x > 0 { return true; }
+            return 5;
+        }
+        fn main() -> i32 { return 0; }
+    ";
+    fails_at(src, "'pick' returns i32 here, but bool where it first returns", "5");
+    fails_at(src, "'pick' first returns bool here", "true");
+}
+
+#[test]
+fn rejects_functions_that_need_each_others_return_types_first() {
+    fails_with("
+        fn ping(n as i32) { return pong(n); }
+        fn pong(n as i32) { return ping(n); }
+        fn main() -> i32 { return 0; }
+    ", "the return types of ping and pong can't be inferred, as they call each other before any of their returns");
+}
+
+#[test]
+fn rejects_a_generic_function_without_a_return_type() {
+    fails_with("
+        fn first<T>(a as T, b as T) { return a; }
+        fn main() -> i32 { return first(1, 2); }
+    ", "first is generic, so its return type has to be written");
+}
@@ -0,0 +1,2370 @@
+use std::collections::HashMap;
+
+use crate::ast::Span;
+use crate::ast::Spanned;
+use crate::ast::Expr;
+use crate::ast::ForHeader;
+use crate::ast::FormatPart;
+use crate::ast::Value;
+use crate::ast::BinaryOp;
+use crate::ast::UnaryOp;
+use crate::ast::Type;
+use crate::ast::Error;
+use crate::ast::FunctionSignature;
+use crate::ast::FunctionDefinition;
+use crate::ast::Class;
+use crate::ast::Field;
+use crate::ast::Union;
+use crate::ast::Variant;
+use crate::ast::Pattern;
+use crate::ast::method_symbol;
+use crate::ast::instance_symbol;
+use crate::ast::constructor_symbol;
+use crate::ast::destructor_symbol;
+use crate::ast::class_ancestry;
+use crate::ast::all_fields;
+use crate::ast::first_field_index;
+
+
+/// What inferring the return type of a function found.
+pub enum Inferred {
+    Known(Type),
+    // a call needs the return type of a function that isn't known yet, so the function has to wait for it.
+    // what it returns is already known if one of its returns came before the call
+    Waiting(String, Option<Type>),
+}
+
+/// The returns of a function whose return type is being inferred that have been checked so far, by the types they
+/// return and where they are. literals without a suffix are kept apart, as the same literal can be of several types
+struct Returns {
+    symbol: String,
+    typed: Vec<(Type, Span)>,
+    literals: Vec<(Type, Span)>,
+}
+
+impl Returns {
+    /// The return that decides the return type so far. a literal only decides it if nothing else does,
+    /// and then a float literal does, as the integer literals can be floats too
+    fn deciding(&self) -> Option<&(Type, Span)> {
+        self.typed
+            .first()
+            .or_else(|| self.literals.iter().find(|(typ, _)| typ.is_float()))
+            .or(self.literals.first())
+    }
+}
+
+/// Resolves the type of every expression in a function before any LLVM IR is built,
+/// so that the compiler can assume it is working with a well-typed tree.
+pub struct TypeChecker<'a> {
+    funcs: &'a HashMap<String, FunctionSignature>,
+    classes: &'a HashMap<String, Class>,
+    unions: &'a HashMap<String, Union>,
+    scopes: Vec<HashMap<String, Type>>,
+    return_type: Type,
+    // the type that the method being checked belongs to, if it is a method
+    owner: Option<Type>,
+    // whether the method being checked is static, and so has no 'this'
+    in_static_method: bool,
+    // how many loops the expression being checked is nested in, for break and continue
+    loop_depth: usize,
+    // the types of the targets of the assignments being checked, which their compound values read
+    assigned_types: Vec<Type>,
+    // the variables of the functions that the closure being checked is inside of, which it can only use if it captures them
+    uncaptured: Vec<String>,
+    // the variables that hold closures which capture, or objects that do. what a closure captures is kept in the frame
+    // of the function that made it, so such a variable can't outlive the function
+    frame_bound: Vec<String>,
+    // the variables that the closure being checked captures, which are kept outside of its own frame
+    captures: Vec<String>,
+    // the returns of the function whose return type is being inferred
+    inferring: Option<Returns>,
+    // the function whose return type a call needed before it was inferred, which the function making the call has to wait for
+    awaited: Option<String>,
+    // errors that point out more of the one that stopped checking, such as where the arms a match is missing would go
+    notes: Vec<Error>,
+    // the generic functions that calls have been checked against, by their symbols, and the type arguments
+    // they were called with. each of them needs an instance to be made, checked and compiled
+    pub instances: Vec<(String, Vec<Type>)>,
+    // the instances of generic classes that the types of calls of generic functions turned out to use,
+    // as List<T> does when T is i32. a function that uses one that doesn't exist yet is checked again once it does
+    pub class_instances: Vec<(String, Vec<Type>)>,
+    // the symbols of the functions and methods that calls have been checked against. the methods of
+    // generic classes are only instantiated for the instances they are called on
+    pub called: Vec<String>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(
+        funcs: &'a HashMap<String, FunctionSignature>,
+        classes: &'a HashMap<String, Class>,
+        unions: &'a HashMap<String, Union>,
+    ) -> Self {
+        TypeChecker {
+            funcs,
+            classes,
+            unions,
+            scopes: Vec::new(),
+            return_type: Type::Void,
+            owner: None,
+            in_static_method: false,
+            loop_depth: 0,
+            assigned_types: Vec::new(),
+            uncaptured: Vec::new(),
+            frame_bound: Vec::new(),
+            captures: Vec::new(),
+            inferring: None,
+            awaited: None,
+            notes: Vec::new(),
+            instances: Vec::new(),
+            class_instances: Vec::new(),
+            called: Vec::new(),
+        }
+    }
+
+    /// Checks that a type written in the source refers to a type that exists.
+    fn resolve_type(&self, typ: &Type, span: &Span) -> Result<Type, Error> {
+        match typ {
+            Type::Named(name) if self.classes.contains_key(name) || self.unions.contains_key(name) => Ok(typ.clone()),
+            Type::Pointer(pointee) => Ok(Type::Pointer(Box::new(self.resolve_type(pointee, span)?))),
+            Type::Function(params, return_type) => Ok(Type::Function(
+                params.iter().map(|param| self.resolve_value_type(param, span)).collect::<Result<_, _>>()?,
+                Box::new(self.resolve_type(return_type, span)?),
+            )),
+            Type::Named(name) => Err(Error {
+                span: span.clone(),
+                msg: format!("unknown type {}", name),
+            }),
+            typ => Ok(typ.clone()),
+        }
+    }
+
+    /// Like `resolve_type`, but for types that have to hold a value, such as variables and parameters.
+    fn resolve_value_type(&self, typ: &Type, span: &Span) -> Result<Type, Error> {
+        match self.resolve_type(typ, span)? {
+            Type::Void => Err(Error {
+                span: span.clone(),
+                msg: "values cannot be of type void".to_string(),
+            }),
+            typ => Ok(typ),
+        }
+    }
+
+    fn expect_type(&self, expected: &Type, found: &Type, span: &Span) -> Result<(), Error> {
+        if expected == found {
+            Ok(())
+        } else {
+            Err(Error {
+                span: span.clone(),
+                msg: format!("mismatched types: expected {}, found {}", expected, found),
+            })
+        }
+    }
+
+    fn lookup_variable(&self, name: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+
+    /// Whether an expression is a variable declared in the function being checked, or in one of the given blocks
+    /// that have been left since, rather than a parameter, whose object belongs to the caller.
+    /// parameters are in the outermost scope
+    fn is_own_variable(&self, expr: &Spanned<Expr>, declared: &[&String]) -> bool {
+        match &expr.0 {
+            Expr::Local(name) => {
+                declared.contains(&name) || self.scopes.iter().rposition(|scope| scope.contains_key(name)).is_some_and(|index| index > 0)
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks that the object an expression results in can be moved to where it is going, such as into a variable.
+    /// an object that needs destroying can only be moved out of a variable, which then doesn't destroy it. anything
+    /// else would still destroy its own copy of the object, so it would be destroyed twice
+    fn check_move(&self, expr: &Spanned<Expr>, typ: &Type) -> Result<(), Error> {
+        let mut moved = expr;
+        let mut declared = Vec::new();
+        loop {
+            match &moved.0 {
+                Expr::Block(body) => moved = body,
+                Expr::Then(a, b) => {
+                    if let Expr::Var(name, _, _) = &a.0 {
+                        declared.push(name);
+                    }
+                    moved = b;
+                },
+                _ => break,
+            }
+        }
+
+        if is_place(moved) && !self.is_own_variable(moved, &declared) && self.has_destructor(typ, &mut Vec::new()) {
+            return Err(Error {
+                span: moved.1.clone(),
+                msg: format!("{} needs destroying, so can only be moved out of a variable, as this copy of it would be destroyed twice", typ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Like `expect_type`, but a pointer to a class is also accepted where a pointer to one of
+    /// its base classes is expected, and null where any pointer is. the expression is then
+    /// wrapped in a cast to the expected type
+    fn coerce(&self, expected: &Type, found: &Type, expr: &mut Spanned<Expr>) -> Result<(), Error> {
+        let is_null_pointer = matches!((expected, &expr.0), (Type::Pointer(_), Expr::Value(Value::Null)));
+
+        if !self.is_upcast(found, expected) && !is_null_pointer {
+            return self.expect_type(expected, found, &expr.1);
+        }
+
+        let span = expr.1.clone();
+        let inner = std::mem::replace(expr, (Expr::Error, span.clone()));
+        *expr = (Expr::Cast(Box::new(inner), expected.clone(), Some(found.clone())), span);
+
+        Ok(())
+    }
+
+    /// Whether one type is a pointer to a class, and the other a pointer to one of its base classes.
+    fn is_upcast(&self, from: &Type, to: &Type) -> bool {
+        match (from, to) {
+            (Type::Pointer(from), Type::Pointer(to)) => match (&**from, &**to) {
+                (Type::Named(derived), Type::Named(base)) => derived != base
+                    && class_ancestry(self.classes, derived).iter().any(|class| &class.name == base),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn has_field(&self, typ: &Type, field_name: &str) -> bool {
+        match typ {
+            Type::Named(name) => all_fields(self.classes, name).iter().any(|field| field.name == field_name),
+            _ => false,
+        }
+    }
+
+    /// Finds a static field of a type, which a class can inherit from its base classes.
+    /// the type that the field belongs to is returned along with it
+    fn find_static_field(&self, typ: &Type, name: &str) -> Option<(Type, &'a Field)> {
+        let class_name = match typ {
+            Type::Named(class_name) => class_name,
+            _ => return None,
+        };
+
+        class_ancestry(self.classes, class_name)
+            .into_iter()
+            .find_map(|class| {
+                class.fields
+                    .iter()
+                    .find(|field| field.is_static && field.name == name)
+                    .map(|field| (Type::Named(class.name.clone()), field))
+            })
+    }
+
+    /// The type that an expression names, if it is the name of a type rather than of a variable,
+    /// as in Type.method().
+    fn named_type(&self, expr: &Spanned<Expr>) -> Option<Type> {
+        match &expr.0 {
+            Expr::Local(name) if self.lookup_variable(name).is_none() => match Type::from_name(name) {
+                Type::Named(name) if !self.classes.contains_key(&name) && !self.unions.contains_key(&name) => None,
+                typ => Some(typ),
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks that something that belongs to an instance, such as a field, isn't used from a static method.
+    fn expect_instance(&self, name: &str, span: &Span) -> Result<(), Error> {
+        if self.in_static_method {
+            Err(Error {
+                span: span.clone(),
+                msg: format!("{} is not static, so cannot be used in a static method", name),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Finds a method of a type. classes also have the methods of their base classes, unless they override them.
+    /// the type that the method found belongs to is returned along with its signature
+    fn find_method(&self, typ: &Type, name: &str) -> Option<(Type, &'a FunctionSignature)> {
+        let owners = match typ {
+            Type::Named(class_name) if self.classes.contains_key(class_name) => class_ancestry(self.classes, class_name)
+                .iter()
+                .map(|class| Type::Named(class.name.clone()))
+                .collect(),
+            _ => vec![typ.clone()],
+        };
+
+        owners
+            .into_iter()
+            .find_map(|owner| self.funcs.get(&method_symbol(&owner, name)).map(|signature| (owner, signature)))
+    }
+
+    fn is_union(&self, typ: &Type) -> bool {
+        matches!(typ, Type::Named(name) if self.unions.contains_key(name))
+    }
+
+    /// Finds a variant of a union by its name, along with its tag.
+    fn find_variant(&self, typ: &Type, name: &str) -> Option<(usize, &'a Variant)> {
+        match typ {
+            Type::Named(union_name) => self.unions.get(union_name)?.variant(name),
+            _ => None,
+        }
+    }
+
+    /// Whether the callee of a call is a function value, such as a variable holding a closure,
+    /// rather than the name of a function.
+    fn is_function_value(&self, func: &Spanned<Expr>) -> bool {
+        match &func.0 {
+            Expr::Local(name) => self.lookup_variable(name).is_some()
+                || self.owner.as_ref().is_some_and(|owner| self.has_field(owner, name) || self.find_static_field(owner, name).is_some()),
+            Expr::GenericName(..) => false,
+            _ => true,
+        }
+    }
+
+    /// Rewrites names that don't refer to a variable or function into what they do refer to.
+    /// calling the name of a class constructs an instance of it, and the static fields and methods
+    /// of a type, as well as the variants of a union, are reached through its name, as in Type.method(). inside a method, the fields and
+    /// methods of its type can be used without writing 'this.' or the type's name
+    fn resolve_names(&self, expr: &mut Spanned<Expr>) -> Result<(), Error> {
+        let span = expr.1.clone();
+        let this = Box::new((Expr::Local("this".to_string()), span.clone()));
+
+        let resolved = match (&mut expr.0, &self.owner) {
+            (Expr::Local(name), Some(owner)) if self.lookup_variable(name).is_none() => {
+                if let Some((field_owner, _)) = self.find_static_field(owner, name) {
+                    Some(Expr::StaticField(field_owner, name.clone()))
+                } else if self.has_field(owner, name) {
+                    self.expect_instance(name, &span)?;
+                    Some(Expr::Field(this, name.clone(), None))
+                } else {
+                    None
+                }
+            },
+            // inside a method, the methods of its type come before functions, as its fields do
+            (Expr::Call(func, args), owner) => match &func.0 {
+                Expr::Local(name) if self.lookup_variable(name).is_some() => None,
+                Expr::Local(name) if owner.as_ref().is_some_and(|owner| self.find_method(owner, name).is_some()) => {
+                    match self.find_method(owner.as_ref().unwrap(), name).unwrap() {
+                        (method_owner, signature) if signature.is_static => {
+                            let symbol = (Expr::Local(method_symbol(&method_owner, name)), func.1.clone());
+                            Some(Expr::Call(Box::new(symbol), std::mem::take(args)))
+                        },
+                        _ => {
+                            self.expect_instance(name, &func.1)?;
+                            Some(Expr::MethodCall(this, name.clone(), std::mem::take(args), None))
+                        },
+                    }
+                },
+                Expr::Local(name) if self.funcs.contains_key(name) => None,
+                Expr::Local(name) if self.classes.contains_key(name) => {
+                    Some(Expr::Construct(Type::Named(name.clone()), std::mem::take(args), None))
+                },
+                Expr::Local(name) if name == "print" || name == "println" => {
+                    Some(Expr::Print(std::mem::take(args), Vec::new(), name == "println"))
+                },
+                _ => None,
+            },
+            (Expr::Field(obj, name, _), _) => match self.named_type(obj) {
+                Some(typ) if self.find_variant(&typ, name).is_some() => Some(Expr::Variant(typ, name.clone(), Vec::new(), None)),
+                Some(typ) if self.is_union(&typ) => return Err(Error {
+                    span: span.clone(),
+                    msg: format!("{} has no variant named {}", typ, name),
+                }),
+                Some(typ) => match self.find_static_field(&typ, name) {
+                    Some((field_owner, _)) => Some(Expr::StaticField(field_owner, name.clone())),
+                    None => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no static field named {}", typ, name),
+                    }),
+                },
+                None => None,
+            },
+            (Expr::MethodCall(obj, name, args, _), _) => match self.named_type(obj) {
+                Some(typ) if self.find_variant(&typ, name).is_some() => {
+                    Some(Expr::Variant(typ, name.clone(), std::mem::take(args), None))
+                },
+                Some(typ) if self.is_union(&typ) && self.find_method(&typ, name).is_none() => return Err(Error {
+                    span: span.clone(),
+                    msg: format!("{} has no variant named {}", typ, name),
+                }),
+                Some(typ) => match self.find_method(&typ, name) {
+                    Some((method_owner, signature)) if signature.is_static => {
+                        let symbol = (Expr::Local(method_symbol(&method_owner, name)), obj.1.start..span.end);
+                        Some(Expr::Call(Box::new(symbol), std::mem::take(args)))
+                    },
+                    Some(_) => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} is not static, so has to be called on an instance of {}", name, typ),
+                    }),
+                    None => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no method named {}", typ, name),
+                    }),
+                },
+                None => None,
+            },
+            _ => None,
+        };
+
+        if let Some(resolved) = resolved {
+            expr.0 = resolved;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a type has any constructors, in which case one of them has to be called to create an instance.
+    fn has_constructors(&self, typ: &Type) -> bool {
+        let prefix = method_symbol(typ, "new.");
+        self.funcs.keys().any(|symbol| symbol.starts_with(&prefix))
+    }
+
+    /// Checks that an instance of a type can be created without any arguments,
+    /// as a variable or field without an initial value is.
+    fn check_default_constructible(&self, typ: &Type, span: &Span) -> Result<(), Error> {
+        if self.is_union(typ) {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("{} is a union, so has no default value and has to be given one", typ),
+            });
+        }
+
+        if let Type::Function(..) = typ {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("{} is a function type, so has no default value and has to be given one", typ),
+            });
+        }
+
+        if self.has_constructors(typ) && !self.funcs.contains_key(&constructor_symbol(typ, 0)) {
+            Err(Error {
+                span: span.clone(),
+                msg: format!("no default constructor defined for {}", typ),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether a value of the given type holds a value of the named class or union, either itself,
+    /// as its base class, in one of its fields, or in the payload of one of its variants.
+    fn contains_type(&self, typ: &Type, type_name: &str, visited: &mut Vec<String>) -> bool {
+        match typ {
+            Type::Named(name) if name == type_name => true,
+            Type::Named(name) if class_ancestry(self.classes, name).iter().any(|class| class.name == type_name) => true,
+            Type::Named(name) if !visited.contains(name) => {
+                visited.push(name.clone());
+                let payloads = self.unions.get(name).into_iter().flat_map(|union| union.variants.iter()).flat_map(|variant| variant.payload.iter());
+
+                all_fields(self.classes, name)
+                    .iter()
+                    .map(|field| &field.typ)
+                    .chain(payloads)
+                    .any(|typ| self.contains_type(typ, type_name, visited))
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether destroying a value of the given type does anything, because it,
+    /// one of its base classes or one of its fields has a destructor.
+    fn has_destructor(&self, typ: &Type, visited: &mut Vec<String>) -> bool {
+        match typ {
+            Type::Named(name) if !visited.contains(name) => {
+                visited.push(name.clone());
+
+                class_ancestry(self.classes, name)
+                    .iter()
+                    .any(|class| self.funcs.contains_key(&destructor_symbol(&Type::Named(class.name.clone()))))
+                    || all_fields(self.classes, name).iter().any(|field| self.has_destructor(&field.typ, visited))
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks that the methods a class overrides have the same parameters and return type as the
+    /// methods they override, as they are called in their place through pointers to the base class.
+    fn check_overrides(&self, class: &Class, base: &str) -> Result<(), Error> {
+        let class_type = Type::Named(class.name.clone());
+
+        for method in class.methods.iter() {
+            let signature = &self.funcs[&method_symbol(&class_type, method)];
+
+            if let Some((owner, overridden)) = self.find_method(&Type::Named(base.to_string()), method) {
+                let same_params = signature.params.len() == overridden.params.len()
+                    && signature.params.iter().zip(overridden.params.iter()).skip(1).all(|((_, a), (_, b))| a == b);
+
+                if !same_params || signature.return_type != overridden.return_type {
+                    return Err(Error {
+                        span: signature.span.clone(),
+                        msg: format!("{} overrides {}, so must have the same parameters and return type", method, method_symbol(&owner, method)),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_class(&mut self, class: &mut Class) -> Result<(), Error> {
+        if let Some(base) = &class.base {
+            if !self.classes.contains_key(base) {
+                return Err(Error {
+                    span: class.span.clone(),
+                    msg: format!("unknown class {}", base),
+                });
+            }
+
+            // a class that derives from itself would be infinitely large
+            if class_ancestry(self.classes, base).iter().any(|ancestor| ancestor.name == class.name) {
+                return Err(Error {
+                    span: class.span.clone(),
+                    msg: format!("{} cannot derive from itself", class.name),
+                });
+            }
+
+            // the base class part of an instance is default constructed
+            self.check_default_constructible(&Type::Named(base.clone()), &class.span)?;
+            self.check_overrides(class, base)?;
+        }
+
+        let inherited_fields = match &class.base {
+            Some(base) => all_fields(self.classes, base),
+            None => Vec::new(),
+        };
+
+        for index in 0..class.fields.len() {
+            let (earlier_fields, later_fields) = class.fields.split_at_mut(index);
+            let field = &mut later_fields[0];
+
+            if earlier_fields.iter().chain(inherited_fields.iter().copied()).any(|other| other.name == field.name) {
+                return Err(Error {
+                    span: field.span.clone(),
+                    msg: format!("{} already has a field named {}", class.name, field.name),
+                });
+            }
+
+            let field_type = self.resolve_value_type(&field.typ, &field.span)?;
+
+            if field.is_static {
+                self.check_static_field(&class.name, field, &field_type)?;
+                continue;
+            }
+
+            // a class that contains itself would be infinitely large
+            if self.contains_type(&field_type, &class.name, &mut Vec::new()) {
+                return Err(Error {
+                    span: field.span.clone(),
+                    msg: format!("{} cannot contain itself through its field {}", class.name, field.name),
+                });
+            }
+
+            // initializers can't see any variables
+            match &mut field.initializer {
+                Some(initializer) => {
+                    self.scopes = vec![HashMap::new()];
+                    self.owner = None;
+                    let initializer_type = self.check_expression_as(initializer, &field_type)?;
+                    self.expect_type(&field_type, &initializer_type, &initializer.1)?;
+                },
+                None => self.check_default_constructible(&field_type, &field.span)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the variants of a union. as which variant a union holds is only known when the program runs,
+    /// their payloads can't be destroyed, so can't be of types with destructors
+    pub fn check_union(&self, union: &Union) -> Result<(), Error> {
+        for (index, variant) in union.variants.iter().enumerate() {
+            if union.variants[..index].iter().any(|other| other.name == variant.name) {
+                return Err(Error {
+                    span: variant.span.clone(),
+                    msg: format!("{} already has a variant named {}", union.name, variant.name),
+                });
+            }
+
+            for typ in variant.payload.iter() {
+                let typ = self.resolve_value_type(typ, &variant.span)?;
+
+                if self.has_destructor(&typ, &mut Vec::new()) {
+                    return Err(Error {
+                        span: variant.span.clone(),
+                        msg: format!("{} has a destructor, so cannot be held by a variant of {}", typ, union.name),
+                    });
+                }
+
+                // a union that contains itself would be infinitely large
+                if self.contains_type(&typ, &union.name, &mut Vec::new()) {
+                    return Err(Error {
+                        span: variant.span.clone(),
+                        msg: format!("{} cannot contain itself through its variant {}", union.name, variant.name),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks a static field, which is a global. its value has to be known before the program runs,
+    /// so it can only be initialised with a literal, and can't be of a class type
+    fn check_static_field(&mut self, class_name: &str, field: &mut Field, field_type: &Type) -> Result<(), Error> {
+        if let Type::Named(_) = field_type {
+            return Err(Error {
+                span: field.span.clone(),
+                msg: format!("static fields cannot be of class type {}", field_type),
+            });
+        }
+
+        if self.funcs.contains_key(&method_symbol(&Type::Named(class_name.to_string()), &field.name)) {
+            return Err(Error {
+                span: field.span.clone(),
+                msg: format!("{} already has a method named {}", class_name, field.name),
+            });
+        }
+
+        if let Some(initializer) = &mut field.initializer {
+            if !is_literal(initializer) {
+                return Err(Error {
+                    span: initializer.1.clone(),
+                    msg: "static fields can only be initialised with a literal".to_string(),
+                });
+            }
+
+            self.scopes = vec![HashMap::new()];
+            self.owner = None;
+            let initializer_type = self.check_expression_as(initializer, field_type)?;
+            self.expect_type(field_type, &initializer_type, &initializer.1)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn check_signature(&self, signature: &FunctionSignature) -> Result<(), Error> {
+        for ((_name, param_type), span) in signature.params.iter().zip(signature.param_spans.iter()) {
+            self.resolve_value_type(param_type, span)?;
+        }
+
+        self.resolve_type(&signature.return_type, &signature.return_span)?;
+
+        Ok(())
+    }
+
+    /// Checks the type parameters of a generic function. the rest of it is only checked
+    /// once for each instance of it, when the types its parameters stand for are known
+    pub fn check_generic_params(&self, signature: &FunctionSignature) -> Result<(), Error> {
+        check_type_params(&signature.generic_params, &signature.generic_param_spans)?;
+
+        if let Some(owner) = &signature.owner {
+            return Err(Error {
+                span: signature.span.clone(),
+                msg: format!("methods cannot be generic, but this one belongs to {}", owner),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks the type parameters of a generic class. like a generic function, the rest of it
+    /// is only checked for each instance of it
+    pub fn check_class_generic_params(&self, class: &Class) -> Result<(), Error> {
+        check_type_params(&class.generic_params, &class.generic_param_spans)?;
+
+        if let Some(param) = class.generic_params.iter().find(|param| **param == class.name) {
+            return Err(Error {
+                span: class.span.clone(),
+                msg: format!("the type parameter {} has the same name as its class", param),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn check_function(&mut self, name: &str, func: &mut FunctionDefinition) -> Result<(), Error> {
+        self.check_signature(&func.signature)?;
+        self.enter_function(&func.signature);
+
+        self.check_body(name, &mut func.body)
+    }
+
+    /// Infers the return type of a function that doesn't write one. it returns what its first return does,
+    /// or the value its body ends in, which the rest have to agree with, or nothing if it has none.
+    /// literals without a suffix only decide it when nothing else does. the function is checked as it is
+    /// once its return type is known, so it is left untouched here
+    pub fn infer_return_type(&mut self, symbol: &str, name: &str, func: &FunctionDefinition) -> Result<Inferred, Vec<Error>> {
+        self.check_signature(&func.signature).map_err(|error| vec![error])?;
+        self.enter_function(&func.signature);
+
+        self.inferring = Some(Returns { symbol: symbol.to_string(), typed: Vec::new(), literals: Vec::new() });
+        let result = self.check_body(name, &mut func.body.clone());
+        let returns = self.inferring.take().unwrap();
+
+        if let Some(awaited) = self.awaited.take() {
+            return Ok(Inferred::Waiting(awaited, returns.deciding().map(|(typ, _)| typ.clone())));
+        }
+        result.map_err(|error| self.with_notes(error))?;
+
+        let (first_type, first_span) = match returns.deciding() {
+            Some(first) => first,
+            None => return Ok(Inferred::Known(Type::Void)),
+        };
+
+        // an integer literal can be of any number type, and a float literal of any float type
+        let conflict = returns.typed
+            .iter()
+            .find(|(typ, _)| typ != first_type)
+            .or_else(|| returns.literals.iter().find(|(typ, _)| match typ.is_float() {
+                true => !first_type.is_float(),
+                false => !first_type.is_numeric(),
+            }));
+
+        match conflict {
+            Some((typ, span)) => Err(vec![
+                Error {
+                    span: span.clone(),
+                    msg: format!("'{}' returns {} here, but {} where it first returns, so has no one return type", name, typ, first_type),
+                },
+                Error {
+                    span: first_span.clone(),
+                    msg: format!("'{}' first returns {} here", name, first_type),
+                },
+            ]),
+            None => Ok(Inferred::Known(first_type.clone())),
+        }
+    }
+
+    /// Records a value that the function whose return type is being inferred returns.
+    fn record_return(&mut self, ret_type: Type, value: &mut Spanned<Expr>, untyped: bool) {
+        let first = self.inferring.as_ref().and_then(|returns| returns.typed.first()).map(|(typ, _)| typ.clone());
+
+        // a value that converts to the type the returns before it return, as a null pointer does, agrees with it
+        let ret_type = match first {
+            Some(first) if !untyped && self.coerce(&first, &ret_type, value).is_ok() => first,
+            _ => ret_type,
+        };
+
+        let returns = self.inferring.as_mut().unwrap();
+        if untyped {
+            returns.literals.push((ret_type, value.1.clone()));
+        } else {
+            returns.typed.push((ret_type, value.1.clone()));
+        }
+    }
+
+    /// Puts the parameters of a function in scope, ready to check its body.
+    fn enter_function(&mut self, signature: &FunctionSignature) {
+        let mut params = HashMap::new();
+        for (param_name, param_type) in signature.params.iter() {
+            params.insert(param_name.clone(), param_type.clone());
+        }
+
+        self.scopes = vec![params, HashMap::new()];
+        self.return_type = signature.return_type.clone();
+        self.owner = signature.owner.clone();
+        self.in_static_method = signature.is_static;
+        self.loop_depth = 0;
+        self.frame_bound.clear();
+        self.captures.clear();
+    }
+
+    /// Whether an expression results in a closure that captures, or in something that may hold one,
+    /// which can't outlive the frame of the function that made the closure.
+    fn is_frame_bound(&self, expr: &Spanned<Expr>) -> bool {
+        match &expr.0 {
+            Expr::Lambda(lambda) => !lambda.captures.is_empty(),
+            Expr::Local(name) => self.frame_bound.contains(name),
+            Expr::Block(body) | Expr::Then(_, body) | Expr::Field(body, _, _) => self.is_frame_bound(body),
+            _ => false,
+        }
+    }
+
+    /// The variable in the frame of the function being checked that a place belongs to, unless it is reached through a pointer.
+    fn frame_variable<'e>(&self, place: &'e Spanned<Expr>) -> Option<&'e String> {
+        match &place.0 {
+            Expr::Local(name) if !self.captures.contains(name) => Some(name),
+            Expr::Field(obj, _, _) => self.frame_variable(obj),
+            _ => None,
+        }
+    }
+
+    /// Checks that a value being returned isn't a closure that would outlive what it captures.
+    fn check_not_frame_bound(&self, value: &Spanned<Expr>) -> Result<(), Error> {
+        if self.is_frame_bound(value) {
+            return Err(Error {
+                span: value.1.clone(),
+                msg: "a closure that captures variables only lives as long as the call that made it, so cannot be returned".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The type that a call of a function returns. a function whose return type is being inferred can call
+    /// itself once one of its returns has said what it returns, but any other whose return type isn't known
+    /// yet has to be waited for
+    fn return_type_of(&mut self, symbol: &str, signature: &FunctionSignature, span: &Span) -> Result<Type, Error> {
+        if !signature.infers_return {
+            return Ok(signature.return_type.clone());
+        }
+
+        if let Some(returns) = &self.inferring {
+            if let Some((typ, _)) = returns.deciding().filter(|_| returns.symbol == symbol) {
+                return Ok(typ.clone());
+            }
+        }
+
+        self.awaited = Some(symbol.to_string());
+        Err(Error {
+            span: span.clone(),
+            msg: format!("the return type of {} is not known yet", symbol),
+        })
+    }
+
+    /// The type of a function used as a value.
+    fn function_type(&mut self, symbol: &str, span: &Span) -> Result<Type, Error> {
+        let signature = &self.funcs[symbol];
+        let params = signature.params.iter().map(|(_, typ)| typ.clone()).collect();
+        Ok(Type::Function(params, Box::new(self.return_type_of(symbol, signature, span)?)))
+    }
+
+    /// Checks the body of a function or closure, once its parameters are in scope and its return type is set.
+    fn check_body(&mut self, name: &str, body: &mut Spanned<Expr>) -> Result<(), Error> {
+        // the value the body ends in is returned, so a literal takes on the return type, as it would in a return
+        infer_literal(tail_of(body), &self.return_type);
+        let untyped_tail = is_untyped_literal(tail_of(body));
+
+        let body_type = self.check_expression(body)?;
+
+        // a function whose return type is being inferred also returns the value its body ends in, if it gets there
+        if self.inferring.is_some() && body_type != Type::Void && !always_returns(body) {
+            self.record_return(body_type.clone(), tail_of(body), untyped_tail);
+        }
+
+        // a function that returns a value has to return it on every path
+        if self.return_type != Type::Void && !self.ends_in_return_value(&self.return_type.clone(), &body_type, body) {
+            return Err(Error {
+                span: body.1.clone(),
+                msg: format!("'{}' must return a value of type {}", name, self.return_type),
+            });
+        }
+        self.check_move(body, &self.return_type)?;
+        if self.return_type != Type::Void {
+            self.check_not_frame_bound(body)?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes the error that stopped checking, followed by the notes that point out more of it.
+    pub fn with_notes(&mut self, error: Error) -> Vec<Error> {
+        std::iter::once(error).chain(self.notes.drain(..)).collect()
+    }
+
+    /// Whether a body that doesn't always return ends in a value of the type its function returns.
+    /// the value is converted to the return type where it can be, as a returned value is
+    fn ends_in_return_value(&self, return_type: &Type, body_type: &Type, body: &mut Spanned<Expr>) -> bool {
+        always_returns(body) || self.coerce(return_type, body_type, tail_of(body)).is_ok()
+    }
+
+    /// Checks an expression and returns its type.
+    /// types that the source leaves out, such as those of var declarations, are filled in.
+    pub fn check_expression(&mut self, expr: &mut Spanned<Expr>) -> Result<Type, Error> {
+        let span = expr.1.clone();
+
+        self.resolve_names(expr)?;
+
+        match &mut expr.0 {
+
+            // Error expressions only get created by parser errors, so cannot exist in a valid AST
+            Expr::Error => unreachable!(),
+
+            Expr::Value(val) => {
+                match val {
+                    Value::Null => Ok(Type::Void),
+                    Value::Bool(_) => Ok(Type::Bool),
+                    Value::Int(..) | Value::Float(..) => self.check_literal(val, false, &span),
+                    Value::Str(_) => Ok(Type::Str),
+                    Value::Func(name) => self.function_type(name, &span),
+                    Value::List(_) => Err(Error {
+                        span: span.clone(),
+                        msg: format!("'{}' is not supported yet", val),
+                    }),
+                }
+            }
+
+            Expr::List(_items) => Err(Error {
+                span: span.clone(),
+                msg: "lists are not supported yet".to_string(),
+            }),
+
+            Expr::Local(name) => {
+                match (self.lookup_variable(name), self.funcs.get(name.as_str())) {
+                    (Some(typ), _) => Ok(typ.clone()),
+                    // a function's name that isn't called is a function value, which can be called later
+                    (None, Some(signature)) if signature.generic_params.is_empty() => {
+                        let typ = self.function_type(name, &span)?;
+                        self.called.push(name.clone());
+                        expr.0 = Expr::Value(Value::Func(name.clone()));
+                        Ok(typ)
+                    },
+                    (None, Some(_)) => Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} is generic, so can only be called, rather than used as a value", name),
+                    }),
+                    (None, None) if self.uncaptured.contains(name) => Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} belongs to the enclosing function, so has to be captured to be used in the closure, as in fn[{}]", name, name),
+                    }),
+                    (None, None) => Err(Error {
+                        span: span.clone(),
+                        msg: format!("The variable named {} does not exist.", name),
+                    }),
+                }
+            }
+
+            Expr::Var(var_name, typ, val) => {
+                let var_type = match (typ.as_ref(), &mut *val) {
+                    (Some((typ, typ_span)), Some(val)) => {
+                        let typ = self.resolve_value_type(typ, typ_span)?;
+                        let val_type = self.check_expression_as(val, &typ)?;
+                        self.coerce(&typ, &val_type, val)?;
+                        typ
+                    },
+                    (Some((typ, typ_span)), None) => {
+                        let typ = self.resolve_value_type(typ, typ_span)?;
+                        self.check_default_constructible(&typ, &span)?;
+                        typ
+                    },
+                    (None, Some(val)) => {
+                        let val_type = self.check_expression(val)?;
+                        self.resolve_value_type(&val_type, &val.1)?
+                    },
+                    (None, None) => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("'{}' needs either a type or a value", var_name),
+                    }),
+                };
+
+                if let Some(val) = val {
+                    self.check_move(val, &var_type)?;
+                    if self.is_frame_bound(val) {
+                        self.frame_bound.push(var_name.clone());
+                    }
+                }
+
+                // a type filled in here comes from the value
+                if typ.is_none() {
+                    *typ = Some((var_type.clone(), val.as_ref().unwrap().1.clone()));
+                }
+                self.scopes.last_mut().unwrap().insert(var_name.clone(), var_type);
+
+                Ok(Type::Void)
+            }
+
+            Expr::Then(a, b) => {
+                self.check_expression(a)?;
+                self.check_expression(b)
+            }
+
+            Expr::Block(body) => {
+                self.scopes.push(HashMap::new());
+                let body_type = self.check_expression(body);
+                self.scopes.pop();
+
+                body_type
+            }
+
+            Expr::Binary(a, op, b, operand_type) => {
+                let (lhs, rhs) = self.check_pair(a, b)?;
+                self.coerce(&lhs, &rhs, b)?;
+                *operand_type = Some(lhs.clone());
+
+                match op {
+                    BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                        if lhs.is_numeric() {
+                            Ok(lhs)
+                        } else {
+                            Err(Error {
+                                span: span.clone(),
+                                msg: format!("arithmetic is not defined for {}", lhs),
+                            })
+                        }
+                    }
+                    BinaryOp::Eq | BinaryOp::NotEq => {
+                        // pointers are equal if they point to the same place
+                        if lhs.is_numeric() || lhs == Type::Bool || lhs == Type::Char || matches!(lhs, Type::Pointer(_)) {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(Error {
+                                span: span.clone(),
+                                msg: format!("equality is not defined for {}", lhs),
+                            })
+                        }
+                    }
+                    BinaryOp::Lt | BinaryOp::Gt | BinaryOp::LtEq | BinaryOp::GtEq => {
+                        if lhs.is_numeric() || lhs == Type::Char {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(Error {
+                                span: span.clone(),
+                                msg: format!("ordering is not defined for {}", lhs),
+                            })
+                        }
+                    }
+                    BinaryOp::And | BinaryOp::Or => {
+                        self.expect_type(&Type::Bool, &lhs, &a.1)?;
+                        Ok(Type::Bool)
+                    }
+                }
+            }
+
+            Expr::Unary(op, a) => {
+                // a negated literal can go one further than a positive one, as in -128i8
+                let operand = match (&op, &mut a.0) {
+                    (UnaryOp::Neg, Expr::Value(val @ (Value::Int(..) | Value::Float(..)))) => self.check_literal(val, true, &a.1)?,
+                    _ => self.check_expression(a)?,
+                };
+
+                match op {
+                    UnaryOp::Not => {
+                        self.expect_type(&Type::Bool, &operand, &a.1)?;
+                        Ok(Type::Bool)
+                    }
+                    UnaryOp::Neg => {
+                        if operand.is_signed() {
+                            Ok(operand)
+                        } else {
+                            Err(Error {
+                                span: span.clone(),
+                                msg: format!("negation is not defined for {}", operand),
+                            })
+                        }
+                    }
+                    UnaryOp::Ref => {
+                        if !is_place(a) {
+                            return Err(Error {
+                                span: a.1.clone(),
+                                msg: "cannot take the address of this expression".to_string(),
+                            });
+                        }
+
+                        Ok(Type::Pointer(Box::new(operand)))
+                    }
+                    UnaryOp::Deref => match operand {
+                        Type::Pointer(pointee) => Ok(*pointee),
+                        _ => Err(Error {
+                            span: span.clone(),
+                            msg: format!("{} is not a pointer, and cannot be dereferenced", operand),
+                        }),
+                    },
+                }
+            }
+
+            Expr::Cast(val, typ, from) => {
+                // void has no values, so nothing can be cast to or from it
+                if *typ == Type::Void {
+                    return Err(Error {
+                        span: span.clone(),
+                        msg: "cannot cast to void, as it has no values".to_string(),
+                    });
+                }
+
+                let typ = self.resolve_value_type(typ, &span)?;
+                let val_type = self.check_expression(val)?;
+
+                if val_type == Type::Void {
+                    return Err(Error {
+                        span: val.1.clone(),
+                        msg: format!("this has no value, so cannot be cast to {}", typ),
+                    });
+                }
+
+                // numbers can be cast to each other, and chars and bools to integers.
+                // it takes an explicit comparison to turn something into a bool
+                let allowed = val_type == typ
+                    || self.is_upcast(&val_type, &typ)
+                    || (val_type.is_numeric() && typ.is_numeric())
+                    || (val_type == Type::Char && typ.is_int())
+                    || (val_type.is_int() && typ == Type::Char)
+                    || (val_type == Type::Bool && typ.is_int());
+
+                if !allowed {
+                    return Err(Error {
+                        span: span.clone(),
+                        msg: format!("cannot cast {} to {}", val_type, typ),
+                    });
+                }
+
+                *from = Some(val_type);
+                Ok(typ)
+            }
+
+            Expr::Assign(target, val) => {
+                // only variables and their fields can be assigned to. the target is checked first, so that names which
+                // aren't those of variables are reported as such
+                let target_type = self.check_expression(target)?;
+                if !is_place(target) {
+                    return Err(Error {
+                        span: target.1.clone(),
+                        msg: "cannot assign to this expression".to_string(),
+                    });
+                }
+
+                self.assigned_types.push(target_type.clone());
+                let val_type = self.check_expression_as(val, &target_type);
+                self.assigned_types.pop();
+                self.coerce(&target_type, &val_type?, val)?;
+                self.check_move(val, &target_type)?;
+
+                if self.is_frame_bound(val) {
+                    match self.frame_variable(target) {
+                        Some(name) => self.frame_bound.push(name.clone()),
+                        None => return Err(Error {
+                            span: val.1.clone(),
+                            msg: "a closure that captures variables only lives as long as the call that made it, so can only be stored in its variables".to_string(),
+                        }),
+                    }
+                }
+
+                Ok(Type::Void)
+            }
+
+            Expr::AssignedValue => Ok(self.assigned_types.last().unwrap().clone()),
+
+            Expr::Field(obj, field_name, index) => {
+                let mut obj_type = self.check_expression(obj)?;
+
+                // fields can be reached through a pointer, as in this.field
+                if let Type::Pointer(pointee) = obj_type.clone() {
+                    wrap_unary(UnaryOp::Deref, obj);
+                    obj_type = *pointee;
+                }
+
+                let class_name = match &obj_type {
+                    Type::Named(name) => name,
+                    _ => return Err(Error {
+                        span: obj.1.clone(),
+                        msg: format!("{} has no fields", obj_type),
+                    }),
+                };
+
+                // inherited fields are laid out first, after the vtable pointer if there is one
+                let fields = all_fields(self.classes, class_name);
+                match fields.iter().position(|field| &field.name == field_name) {
+                    Some(position) => {
+                        *index = Some(first_field_index(self.classes, class_name) + position);
+                        Ok(fields[position].typ.clone())
+                    },
+                    None => Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no field named {}", class_name, field_name),
+                    }),
+                }
+            }
+
+            Expr::Index(obj, index) => {
+                let obj_type = self.check_expression(obj)?;
+                if obj_type != Type::Str {
+                    return Err(Error {
+                        span: obj.1.clone(),
+                        msg: format!("{} cannot be indexed", obj_type),
+                    });
+                }
+
+                let index_type = self.check_expression_as(index, &Type::USize)?;
+                self.expect_type(&Type::USize, &index_type, &index.1)?;
+
+                Ok(Type::Char)
+            }
+
+            Expr::Call(func, args) => {
+                // calling a function value, such as a variable holding a closure, calls the function it holds
+                if self.is_function_value(func) {
+                    let name = match &func.0 {
+                        Expr::Local(name) | Expr::Field(_, name, _) => name.clone(),
+                        _ => "function value".to_string(),
+                    };
+
+                    return match self.check_expression(func)? {
+                        Type::Function(params, return_type) => {
+                            let params: Vec<(String, Type)> = params.into_iter().map(|typ| (String::new(), typ)).collect();
+                            self.check_args(&name, &params, args, &span)?;
+                            Ok(*return_type)
+                        },
+                        typ => Err(Error {
+                            span: func.1.clone(),
+                            msg: format!("{} is not a function, so cannot be called", typ),
+                        }),
+                    };
+                }
+
+                let (name, type_args) = match &func.0 {
+                    Expr::Local(name) => (name.clone(), None),
+                    Expr::GenericName(name, type_args) => (name.clone(), Some(type_args.clone())),
+                    _ => return Err(Error {
+                        span: func.1.clone(),
+                        msg: format!("'{:?}' is not callable", func.0),
+                    }),
+                };
+
+                let signature = match self.funcs.get(&name) {
+                    Some(signature) => signature,
+                    None => return Err(Error {
+                        span: func.1.clone(),
+                        msg: format!("The function named {} does not exist.", name),
+                    }),
+                };
+
+                // a call of a generic function calls its instance for the types it is given
+                if !signature.generic_params.is_empty() {
+                    let (symbol, return_type) = self.check_generic_call(&name, signature, type_args, args, &func.1, &span)?;
+                    func.0 = Expr::Local(symbol);
+                    return Ok(return_type);
+                }
+
+                if type_args.is_some() {
+                    return Err(Error {
+                        span: func.1.clone(),
+                        msg: format!("{} is not generic, so cannot be given type arguments", name),
+                    });
+                }
+
+                self.check_args(&name, &signature.params, args, &span)?;
+                let return_type = self.return_type_of(&name, signature, &span)?;
+                self.called.push(name);
+
+                Ok(return_type)
+            }
+
+            Expr::GenericName(name, _) => Err(Error {
+                span: span.clone(),
+                msg: format!("{} can only be given type arguments where it is called", name),
+            }),
+
+            Expr::MethodCall(obj, name, args, method_owner) => {
+                let obj_type = self.check_expression(obj)?;
+
+                // a field that holds a function value is called like a method, as in button.on_click(x)
+                let pointee = match &obj_type {
+                    Type::Pointer(pointee) => (**pointee).clone(),
+                    _ => obj_type.clone(),
+                };
+                if self.find_method(&pointee, name).is_none() && self.has_field(&pointee, name) {
+                    let obj = std::mem::replace(obj, Box::new((Expr::Error, span.clone())));
+                    let field = (Expr::Field(obj, name.clone(), None), span.clone());
+                    expr.0 = Expr::Call(Box::new(field), std::mem::take(args));
+                    return self.check_expression(expr);
+                }
+
+                // methods are given a pointer to the object, which is taken implicitly if the object isn't one already
+                let owner = match &obj_type {
+                    Type::Pointer(pointee) => (**pointee).clone(),
+                    _ => {
+                        wrap_unary(UnaryOp::Ref, obj);
+                        obj_type.clone()
+                    },
+                };
+
+                let (found_owner, signature) = match self.find_method(&owner, name) {
+                    Some(method) => method,
+                    None => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no method named {}", owner, name),
+                    }),
+                };
+
+                if signature.is_static {
+                    return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} is static, so has to be called as {}.{}()", name, found_owner, name),
+                    });
+                }
+
+                // an inherited method is given a pointer to the base class it belongs to
+                self.coerce(&signature.params[0].1, &Type::Pointer(Box::new(owner)), obj)?;
+
+                // the first parameter is 'this'
+                self.check_args(name, &signature.params[1..], args, &span)?;
+
+                let symbol = method_symbol(&found_owner, name);
+                let return_type = self.return_type_of(&symbol, signature, &span)?;
+                self.called.push(symbol);
+                *method_owner = Some(found_owner);
+                Ok(return_type)
+            }
+
+            Expr::Construct(typ, args, symbol) => {
+                let typ = self.resolve_value_type(typ, &span)?;
+
+                // a class without constructors can still be created, with the initial values of its fields
+                if !self.has_constructors(&typ) {
+                    if !args.is_empty() {
+                        return Err(Error {
+                            span: span.clone(),
+                            msg: format!("{} has no constructors, so cannot be given arguments", typ),
+                        });
+                    }
+
+                    return Ok(typ);
+                }
+
+                let constructor = constructor_symbol(&typ, args.len());
+                let signature = match self.funcs.get(&constructor) {
+                    Some(signature) => signature,
+                    None => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no constructor taking {} arguments", typ, args.len()),
+                    }),
+                };
+
+                // the first parameter is 'this'
+                self.check_args(&typ.to_string(), &signature.params[1..], args, &span)?;
+
+                *symbol = Some(constructor);
+                Ok(typ)
+            }
+
+            Expr::New(construct) => {
+                let typ = self.check_expression(construct)?;
+
+                if !matches!(construct.0, Expr::Construct(..)) {
+                    return Err(Error {
+                        span: construct.1.clone(),
+                        msg: "only instances of classes can be created with new".to_string(),
+                    });
+                }
+
+                Ok(Type::Pointer(Box::new(typ)))
+            }
+
+            // only objects made by new can be deleted, and new only makes objects of classes
+            Expr::Delete(ptr, pointee) => {
+                if let Expr::Unary(UnaryOp::Ref, _) = &ptr.0 {
+                    return Err(Error {
+                        span: ptr.1.clone(),
+                        msg: "this points at memory that wasn't made by new, so cannot be deleted".to_string(),
+                    });
+                }
+
+                match self.check_expression(ptr)? {
+                    Type::Pointer(typ) if matches!(&*typ, Type::Named(name) if self.classes.contains_key(name)) => *pointee = Some(*typ),
+                    Type::Pointer(typ) => return Err(Error {
+                        span: ptr.1.clone(),
+                        msg: format!("{} is not a class, so a pointer to it cannot be deleted", typ),
+                    }),
+                    typ => return Err(Error {
+                        span: ptr.1.clone(),
+                        msg: format!("{} is not a pointer, and cannot be deleted", typ),
+                    }),
+                }
+
+                Ok(Type::Void)
+            }
+
+            Expr::Print(args, parts, _) => {
+                // a single value can be printed without a format string, as in println(x)
+                *parts = match args.first() {
+                    Some((Expr::Value(Value::Str(format)), format_span)) => {
+                        let parts = parse_format(format, format_span)?;
+                        args.remove(0);
+                        parts
+                    },
+                    _ if args.len() <= 1 => (0..args.len()).map(|index| FormatPart::Arg(index, None)).collect(),
+                    _ => return Err(Error {
+                        span: args[0].1.clone(),
+                        msg: "the first argument of a print has to be a string literal, which says where the others go".to_string(),
+                    }),
+                };
+
+                for (index, arg) in args.iter_mut().enumerate() {
+                    let arg_type = self.check_expression(arg)?;
+                    let printable = arg_type.is_numeric()
+                        || matches!(arg_type, Type::Bool | Type::Char | Type::Str | Type::Pointer(_));
+                    if !printable {
+                        return Err(Error {
+                            span: arg.1.clone(),
+                            msg: format!("{} cannot be printed", arg_type),
+                        });
+                    }
+
+                    let mut used = false;
+                    for part in parts.iter_mut() {
+                        if let FormatPart::Arg(position, typ) = part {
+                            if *position == index {
+                                *typ = Some(arg_type.clone());
+                                used = true;
+                            }
+                        }
+                    }
+
+                    if !used {
+                        return Err(Error {
+                            span: arg.1.clone(),
+                            msg: format!("this is never printed, as the format string has no {{{}}}", index),
+                        });
+                    }
+                }
+
+                // any argument that is printed has been given its type
+                if let Some(FormatPart::Arg(position, _)) = parts.iter().find(|part| matches!(part, FormatPart::Arg(_, None))) {
+                    return Err(Error {
+                        span: span.clone(),
+                        msg: format!("there is no argument {} to print, as only {} were given", position, args.len()),
+                    });
+                }
+
+                Ok(Type::Void)
+            }
+
+            Expr::Variant(typ, name, args, tag) => {
+                let (index, variant) = self.find_variant(typ, name).unwrap();
+                let params: Vec<(String, Type)> = variant.payload.iter().map(|typ| (String::new(), typ.clone())).collect();
+                self.check_args(&format!("{}.{}", typ, name), &params, args, &span)?;
+
+                *tag = Some(index);
+                Ok(typ.clone())
+            }
+
+            Expr::Is(union, name, tag) => {
+                let union_type = self.check_expression(union)?;
+
+                if !self.is_union(&union_type) {
+                    return Err(Error {
+                        span: union.1.clone(),
+                        msg: format!("{} is not a union, so does not have variants", union_type),
+                    });
+                }
+
+                match self.find_variant(&union_type, name) {
+                    Some((index, _)) => *tag = Some(index),
+                    None => return Err(Error {
+                        span: span.clone(),
+                        msg: format!("{} has no variant named {}", union_type, name),
+                    }),
+                }
+
+                Ok(Type::Bool)
+            }
+
+            Expr::Lambda(lambda) => {
+                let mut captured = HashMap::new();
+                for capture in lambda.captures.iter_mut() {
+                    let typ = match self.lookup_variable(&capture.name) {
+                        Some(typ) => typ.clone(),
+                        None => return Err(Error {
+                            span: capture.span.clone(),
+                            msg: format!("there is no variable named {} to capture", capture.name),
+                        }),
+                    };
+
+                    if captured.contains_key(&capture.name) {
+                        return Err(Error {
+                            span: capture.span.clone(),
+                            msg: format!("{} is captured more than once", capture.name),
+                        });
+                    }
+
+                    // the copy would destroy the object a second time
+                    if !capture.by_reference && self.has_destructor(&typ, &mut Vec::new()) {
+                        return Err(Error {
+                            span: capture.span.clone(),
+                            msg: format!("{} has a destructor, so can only be captured by reference, as &{}", capture.name, capture.name),
+                        });
+                    }
+
+                    capture.typ = Some(typ.clone());
+                    captured.insert(capture.name.clone(), typ);
+                }
+
+                for (param_name, param_type) in lambda.params.iter_mut() {
+                    *param_type = self.resolve_value_type(param_type, &span)?;
+                    captured.insert(param_name.clone(), param_type.clone());
+                }
+                lambda.return_type = self.resolve_type(&lambda.return_type, &span)?;
+
+                // the body is checked like that of a function of its own, which can only use what it is given
+                let enclosing: Vec<String> = self.scopes.iter().flat_map(|scope| scope.keys().cloned()).collect();
+                let uncaptured = std::mem::replace(&mut self.uncaptured, [self.uncaptured.clone(), enclosing].concat());
+                let scopes = std::mem::replace(&mut self.scopes, vec![captured, HashMap::new()]);
+                let return_type = std::mem::replace(&mut self.return_type, lambda.return_type.clone());
+                let owner = self.owner.take();
+                let inferring = self.inferring.take();
+                // the closure's own frame only lasts as long as a call of it, and what it captures is kept outside of that
+                let names: Vec<String> = lambda.captures.iter().map(|capture| capture.name.clone()).collect();
+                let frame_bound = names.iter().filter(|name| self.frame_bound.contains(*name)).cloned().collect();
+                let frame_bound = std::mem::replace(&mut self.frame_bound, frame_bound);
+                let captures = std::mem::replace(&mut self.captures, names);
+                let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                let assigned_types = std::mem::take(&mut self.assigned_types);
+
+                let result = self.check_body("closure", &mut lambda.body);
+
+                self.scopes = scopes;
+                self.return_type = return_type;
+                self.owner = owner;
+                self.inferring = inferring;
+                self.frame_bound = frame_bound;
+                self.captures = captures;
+                self.loop_depth = loop_depth;
+                self.assigned_types = assigned_types;
+                self.uncaptured = uncaptured;
+                result?;
+
+                let params = lambda.params.iter().map(|(_, typ)| typ.clone()).collect();
+                Ok(Type::Function(params, Box::new(lambda.return_type.clone())))
+            }
+
+            Expr::StaticField(owner, name) => {
+                let (_, field) = self.find_static_field(owner, name).unwrap();
+                Ok(field.typ.clone())
+            }
+
+            Expr::If(cond, a, b) => {
+                let cond_type = self.check_expression(cond)?;
+                self.expect_type(&Type::Bool, &cond_type, &cond.1)?;
+
+                // both branches have to agree, so that the if can be used as a value.
+                // an if without an else is void
+                let (a_type, b_type) = self.check_pair(a, b)?;
+                self.check_move(a, &a_type)?;
+                self.check_move(b, &b_type)?;
+
+                // a branch that always returns never produces a value, so the if has the other branch's type
+                if always_returns(a) {
+                    return Ok(b_type);
+                }
+                if always_returns(b) {
+                    return Ok(a_type);
+                }
+
+                // a missing else branch has an empty span, so point at the whole if instead
+                let b_span = if b.1.is_empty() { &span } else { &b.1 };
+                self.expect_type(&a_type, &b_type, b_span)?;
+
+                Ok(a_type)
+            }
+
+            Expr::Match(value, arms, value_type) => {
+                let typ = self.check_expression(value)?;
+
+                if !(typ.is_int() || typ == Type::Bool || self.is_union(&typ)) {
+                    return Err(Error {
+                        span: value.1.clone(),
+                        msg: format!("only integers, bools and unions can be matched on, not {}", typ),
+                    });
+                }
+
+                // the patterns checked so far, which a later pattern has to match some other value than
+                let mut rows: Vec<Vec<Pat>> = Vec::new();
+                let mut match_type: Option<Type> = None;
+
+                for arm in arms.iter_mut() {
+                    let mut bindings = Vec::new();
+                    for pattern in arm.patterns.iter_mut() {
+                        self.check_pattern(pattern, &typ, &mut bindings)?;
+
+                        let row = vec![to_pat(pattern)];
+                        if !is_useful(&rows, &row, &[typ.clone()], self.unions) {
+                            return Err(Error {
+                                span: pattern.1.clone(),
+                                msg: "this pattern is unreachable, as the arms before it match every value it does".to_string(),
+                            });
+                        }
+                        rows.push(row);
+                    }
+
+                    // with more than one pattern, it isn't known which of them a name would be bound by
+                    if let Some((name, _, binding_span)) = bindings.first().filter(|_| arm.patterns.len() > 1) {
+                        return Err(Error {
+                            span: binding_span.clone(),
+                            msg: format!("{} cannot be bound in an arm with more than one pattern", name),
+                        });
+                    }
+
+                    self.scopes.push(bindings.into_iter().map(|(name, typ, _)| (name, typ)).collect());
+                    let body_type = match &match_type {
+                        Some(expected) => self.check_expression_as(&mut arm.body, expected),
+                        None => self.check_expression(&mut arm.body),
+                    };
+                    self.scopes.pop();
+
+                    let body_type = body_type?;
+                    self.check_move(&arm.body, &body_type)?;
+
+                    // like the branches of an if, every arm has to agree, except for the ones that always return
+                    if always_returns(&arm.body) {
+                        continue;
+                    }
+                    match &match_type {
+                        Some(expected) => self.expect_type(expected, &body_type, &arm.body.1)?,
+                        None => match_type = Some(body_type),
+                    }
+                }
+
+                let missing: Vec<String> = missing_cases(&rows, &[typ.clone()], self.unions)
+                    .into_iter()
+                    .map(|mut case| case.remove(0))
+                    .collect();
+                if !missing.is_empty() {
+                    // the missing arms would go at the end of the match, before its closing brace
+                    self.notes.push(Error {
+                        span: span.end - 1..span.end,
+                        msg: format!("the arms that cover {} would go here", missing.join(", ")),
+                    });
+                    return Err(Error {
+                        span: value.1.clone(),
+                        msg: format!("the arms of the match don't cover every {}, such as {}", typ, missing.join(", ")),
+                    });
+                }
+
+                *value_type = Some(typ);
+                Ok(match_type.unwrap_or(Type::Void))
+            }
+
+            Expr::While(cond, body) => {
+                let cond_type = self.check_expression(cond)?;
+                self.expect_type(&Type::Bool, &cond_type, &cond.1)?;
+
+                self.check_loop_body(body)?;
+
+                Ok(Type::Void)
+            }
+
+            Expr::For(header, body) => {
+                // the loop variable is only visible in the header and the body
+                self.scopes.push(HashMap::new());
+                let result = self.check_for_header(header).and_then(|_| self.check_loop_body(body));
+                self.scopes.pop();
+
+                result.map(|_| Type::Void)
+            }
+
+            Expr::Break | Expr::Continue => {
+                if self.loop_depth == 0 {
+                    return Err(Error {
+                        span: span.clone(),
+                        msg: "break and continue can only be used inside of a loop".to_string(),
+                    });
+                }
+
+                Ok(Type::Void)
+            }
+
+            Expr::Ret(ret_expr) => {
+                self.check_not_frame_bound(ret_expr)?;
+
+                // a function whose return type is being inferred returns what its first return does
+                if let Some(returns) = &self.inferring {
+                    let first = returns.typed.first().map(|(typ, _)| typ.clone());
+
+                    if first.is_none() && matches!(ret_expr.0, Expr::Value(Value::Null)) {
+                        return Err(Error {
+                            span: ret_expr.1.clone(),
+                            msg: "null has no type of its own, so the return type has to be written, as in -> Node*".to_string(),
+                        });
+                    }
+
+                    let untyped = is_untyped_literal(ret_expr);
+                    let ret_type = self.check_expression_as(ret_expr, first.as_ref().unwrap_or(&Type::Void))?;
+                    self.record_return(ret_type, ret_expr, untyped);
+                    return Ok(Type::Void);
+                }
+
+                let ret_type = self.check_expression_as(ret_expr, &self.return_type.clone())?;
+                self.coerce(&self.return_type, &ret_type, ret_expr)?;
+                self.check_move(ret_expr, &ret_type)?;
+
+                Ok(Type::Void)
+            }
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the function being called.
+    fn check_args(&mut self, name: &str, params: &[(String, Type)], args: &mut [Spanned<Expr>], span: &Span) -> Result<(), Error> {
+        if params.len() != args.len() {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("'{}' called with wrong number of arguments (expected {}, found {})", name, params.len(), args.len()),
+            });
+        }
+
+        for ((_param_name, param_type), arg) in params.iter().zip(args.iter_mut()) {
+            let arg_type = self.check_expression_as(arg, param_type)?;
+            self.coerce(param_type, &arg_type, arg)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks a call of a generic function, and returns the symbol of the instance it calls along with its return type.
+    /// the types that the function's type parameters stand for are either given, or worked out from the arguments
+    fn check_generic_call( &mut self,
+        name: &str,
+        signature: &FunctionSignature,
+        type_args: Option<Vec<Type>>,
+        args: &mut [Spanned<Expr>],
+        name_span: &Span,
+        span: &Span,
+    ) -> Result<(String, Type), Error> {
+        let generic_params = &signature.generic_params;
+        let mut bindings: HashMap<String, Type> = HashMap::new();
+
+        if let Some(type_args) = type_args {
+            if type_args.len() != generic_params.len() {
+                return Err(Error {
+                    span: name_span.clone(),
+                    msg: format!("'{}' takes {} type arguments, found {}", name, generic_params.len(), type_args.len()),
+                });
+            }
+
+            for (param, type_arg) in generic_params.iter().zip(type_args.iter()) {
+                bindings.insert(param.clone(), self.resolve_value_type(type_arg, name_span)?);
+            }
+        }
+
+        if signature.params.len() != args.len() {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("'{}' called with wrong number of arguments (expected {}, found {})", name, signature.params.len(), args.len()),
+            });
+        }
+
+        // each argument is checked against what is known of its parameter's type so far,
+        // so that in max(1.5, 2) the 2 is taken as a float
+        let mut arg_types = Vec::with_capacity(args.len());
+        for ((_param_name, param_type), arg) in signature.params.iter().zip(args.iter_mut()) {
+            let expected = param_type.substitute(&bindings);
+            let arg_type = if mentions_type_params(&expected, generic_params) {
+                self.check_expression(arg)?
+            } else {
+                let expected = expected.name_instances(&mut self.class_instances);
+                self.check_expression_as(arg, &expected)?
+            };
+
+            infer_type_args(param_type, &arg_type, generic_params, self.classes, &mut bindings);
+            arg_types.push(arg_type);
+        }
+
+        let mut instance_args = Vec::with_capacity(generic_params.len());
+        for param in generic_params.iter() {
+            match bindings.get(param) {
+                Some(typ) => instance_args.push(typ.clone()),
+                None => return Err(Error {
+                    span: name_span.clone(),
+                    msg: format!("cannot work out what {} is for this call of '{}', so it has to be given, as in {}<...>()", param, name, name),
+                }),
+            }
+        }
+
+        for (((_param_name, param_type), arg), arg_type) in signature.params.iter().zip(args.iter_mut()).zip(arg_types.iter()) {
+            let param_type = param_type.substitute(&bindings).name_instances(&mut self.class_instances);
+            self.coerce(&param_type, arg_type, arg)?;
+        }
+
+        let symbol = instance_symbol(name, &instance_args);
+        self.instances.push((name.to_string(), instance_args));
+
+        Ok((symbol, signature.return_type.substitute(&bindings).name_instances(&mut self.class_instances)))
+    }
+
+    /// Like `check_expression`, but literals without a suffix take on the expected type.
+    fn check_expression_as(&mut self, expr: &mut Spanned<Expr>, expected: &Type) -> Result<Type, Error> {
+        infer_literal(expr, expected);
+        self.check_expression(expr)
+    }
+
+    /// Checks two expressions that should have the same type, such as the operands of a binary op.
+    /// if only one of them is a literal without a suffix, it takes on the type of the other
+    fn check_pair(&mut self, a: &mut Spanned<Expr>, b: &mut Spanned<Expr>) -> Result<(Type, Type), Error> {
+        if is_untyped_literal(a) && !is_untyped_literal(b) {
+            let b_type = self.check_expression(b)?;
+            let a_type = self.check_expression_as(a, &b_type)?;
+            Ok((a_type, b_type))
+        } else {
+            let a_type = self.check_expression(a)?;
+            let b_type = self.check_expression_as(b, &a_type)?;
+            Ok((a_type, b_type))
+        }
+    }
+
+    /// Checks that a number literal fits in its type. literals that have no suffix,
+    /// and weren't given a type by their context, are i32 or f64
+    fn check_literal(&self, val: &mut Value, negated: bool, span: &Span) -> Result<Type, Error> {
+        let (fits, typ) = match val {
+            Value::Int(n, typ) => {
+                let typ = typ.get_or_insert(Type::I32).clone();
+
+                // an integer literal can also be used as a float, which it always fits in
+                let fits = match typ.int_width() {
+                    Some((bits, true)) => (*n as u128) < (1u128 << (bits - 1)) + negated as u128,
+                    Some((bits, false)) => (*n as u128) < (1u128 << bits) && (!negated || *n == 0),
+                    None => true,
+                };
+
+                (fits, typ)
+            },
+            Value::Float(x, typ) => {
+                let typ = typ.get_or_insert(Type::F64).clone();
+                ((typ != Type::F32) || (*x as f32).is_finite(), typ)
+            },
+            _ => unreachable!(),
+        };
+
+        if fits {
+            Ok(typ)
+        } else {
+            Err(Error {
+                span: span.clone(),
+                msg: format!("literal out of range for {}", typ),
+            })
+        }
+    }
+
+    /// Checks a pattern against the type of the value it is matched with. names of the variants of a union being
+    /// matched are turned into variant patterns, and other names are added to the bindings, along with what they hold
+    fn check_pattern(&mut self, pattern: &mut Spanned<Pattern>, typ: &Type, bindings: &mut Vec<(String, Type, Span)>) -> Result<(), Error> {
+        let span = pattern.1.clone();
+
+        if let Pattern::Binding(name) = &pattern.0 {
+            if self.find_variant(typ, name).is_some() {
+                pattern.0 = Pattern::Variant(name.clone(), Vec::new(), None);
+            }
+        }
+
+        match &mut pattern.0 {
+            Pattern::Wildcard => Ok(()),
+
+            Pattern::Binding(name) => {
+                if bindings.iter().any(|(bound, _, _)| bound == name) {
+                    return Err(Error {
+                        span,
+                        msg: format!("{} is bound more than once in the same pattern", name),
+                    });
+                }
+
+                bindings.push((name.clone(), typ.clone(), span));
+                Ok(())
+            },
+
+            Pattern::Literal(literal) => {
+                if !(typ.is_int() || *typ == Type::Bool) {
+                    return Err(Error {
+                        span,
+                        msg: format!("{} values cannot be matched against literals", typ),
+                    });
+                }
+
+                let literal_type = self.check_expression_as(literal, typ)?;
+                self.expect_type(typ, &literal_type, &span)
+            },
+
+            Pattern::Variant(name, fields, tag) => {
+                if !self.is_union(typ) {
+                    return Err(Error {
+                        span,
+                        msg: format!("{} is not a union, so does not have variants", typ),
+                    });
+                }
+
+                let (index, variant) = match self.find_variant(typ, name) {
+                    Some(found) => found,
+                    None => return Err(Error {
+                        span,
+                        msg: format!("{} has no variant named {}", typ, name),
+                    }),
+                };
+
+                if variant.payload.len() != fields.len() {
+                    return Err(Error {
+                        span,
+                        msg: format!("{}.{} holds {} values, but the pattern gives {}", typ, name, variant.payload.len(), fields.len()),
+                    });
+                }
+
+                *tag = Some(index);
+                for (field, field_type) in fields.iter_mut().zip(variant.payload.iter()) {
+                    self.check_pattern(field, field_type, bindings)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    fn check_loop_body(&mut self, body: &mut Spanned<Expr>) -> Result<(), Error> {
+        self.loop_depth += 1;
+        let result = self.check_expression(body);
+        self.loop_depth -= 1;
+
+        result.map(|_| ())
+    }
+
+    fn check_for_header(&mut self, header: &mut ForHeader) -> Result<(), Error> {
+        match header {
+            ForHeader::Standard(init, cond, step) => {
+                self.check_expression(init)?;
+
+                let cond_type = self.check_expression(cond)?;
+                self.expect_type(&Type::Bool, &cond_type, &cond.1)?;
+
+                self.check_expression(step)?;
+            },
+
+            ForHeader::Range(var_name, typ, start, end, step) => {
+                // without a type, the loop variable takes the type of the range
+                if let Some((typ, typ_span)) = typ {
+                    let typ = self.resolve_value_type(typ, typ_span)?;
+                    infer_literal(start, &typ);
+                    infer_literal(end, &typ);
+                }
+
+                let (start_type, end_type) = self.check_pair(start, end)?;
+                if let Some((typ, _)) = typ {
+                    self.expect_type(typ, &start_type, &start.1)?;
+                }
+
+                if !start_type.is_int() {
+                    return Err(Error {
+                        span: start.1.clone(),
+                        msg: format!("ranges must be made of integers, found {}", start_type),
+                    });
+                }
+
+                self.expect_type(&start_type, &end_type, &end.1)?;
+
+                // a type filled in here comes from the start of the range
+                if typ.is_none() {
+                    *typ = Some((start_type.clone(), start.1.clone()));
+                }
+                self.scopes.last_mut().unwrap().insert(var_name.clone(), start_type);
+
+                // a step that doesn't assign to the variable would leave it where it is, so the loop would never end
+                if let Some(step) = step {
+                    let steps_variable = match &step.0 {
+                        Expr::Assign(target, _) => matches!(&target.0, Expr::Local(name) if name == var_name),
+                        _ => false,
+                    };
+                    if !steps_variable {
+                        return Err(Error {
+                            span: step.1.clone(),
+                            msg: format!("the step of a range has to assign to {0}, as in {0} += 2", var_name),
+                        });
+                    }
+
+                    self.check_expression(step)?;
+                }
+            },
+
+            // there are no collections yet, so the form parses but nothing can be iterated with it
+            ForHeader::Each(_var_name, collection) => {
+                let collection_type = self.check_expression(collection)?;
+
+                return Err(Error {
+                    span: collection.1.clone(),
+                    msg: format!("{} is not a collection that can be iterated. there are no collections to iterate yet", collection_type),
+                });
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a format string, as in "x is {0}", into the text and arguments it prints.
+/// {} prints the argument after the last one that {} printed, and {{ and }} print a brace
+fn parse_format(format: &str, span: &Span) -> Result<Vec<FormatPart>, Error> {
+    let error = |msg: &str| Error {
+        span: span.clone(),
+        msg: msg.to_string(),
+    };
+
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut next_position = 0;
+
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek() == Some(&c) => {
+                chars.next();
+                text.push(c);
+            },
+            '{' => {
+                let mut position = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => position.push(c),
+                        None => return Err(error("unclosed { in format string, which is printed with {{")),
+                    }
+                }
+
+                let position = match position.trim() {
+                    "" => {
+                        next_position += 1;
+                        next_position - 1
+                    },
+                    position => position
+                        .parse()
+                        .map_err(|_| error(&format!("{{{}}} in format string should be the position of an argument, as in {{0}}", position)))?,
+                };
+
+                if !text.is_empty() {
+                    parts.push(FormatPart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(FormatPart::Arg(position, None));
+            },
+            '}' => return Err(error("unmatched } in format string, which is printed with }}")),
+            c => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(FormatPart::Text(text));
+    }
+
+    Ok(parts)
+}
+
+/// Checks that the type parameters of a generic function or class are each declared once, and aren't primitive types.
+fn check_type_params(generic_params: &[String], spans: &[Span]) -> Result<(), Error> {
+    for ((index, param), span) in generic_params.iter().enumerate().zip(spans.iter()) {
+        if generic_params[..index].contains(param) {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("the type parameter {} is declared more than once", param),
+            });
+        }
+
+        if Type::from_name(param) != Type::Named(param.clone()) {
+            return Err(Error {
+                span: span.clone(),
+                msg: format!("{} is a primitive type, so cannot be the name of a type parameter", param),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a type is, points to, or is a generic class of one of the given type parameters.
+fn mentions_type_params(typ: &Type, generic_params: &[String]) -> bool {
+    match typ {
+        Type::Named(name) => generic_params.contains(name),
+        Type::Pointer(pointee) => mentions_type_params(pointee, generic_params),
+        Type::Generic(_, type_args) => type_args.iter().any(|typ| mentions_type_params(typ, generic_params)),
+        Type::Function(params, return_type) => {
+            params.iter().any(|typ| mentions_type_params(typ, generic_params)) || mentions_type_params(return_type, generic_params)
+        },
+        _ => false,
+    }
+}
+
+/// Works out what type parameters stand for from the type of an argument given for a parameter,
+/// as in T being i32 when an i32* is given for a T*, or a List<i32> for a List<T>.
+/// a type parameter that is already known is left as it is
+fn infer_type_args(param_type: &Type, arg_type: &Type, generic_params: &[String], classes: &HashMap<String, Class>, bindings: &mut HashMap<String, Type>) {
+    match (param_type, arg_type) {
+        (Type::Named(name), _) if generic_params.contains(name) => {
+            bindings.entry(name.clone()).or_insert_with(|| arg_type.clone());
+        },
+        (Type::Pointer(param_pointee), Type::Pointer(arg_pointee)) => {
+            infer_type_args(param_pointee, arg_pointee, generic_params, classes, bindings);
+        },
+        (Type::Generic(template, param_args), Type::Named(class_name)) => {
+            if let Some((instance_of, arg_args)) = classes.get(class_name).and_then(|class| class.instance_of.as_ref()) {
+                if instance_of == template {
+                    for (param_arg, arg_arg) in param_args.iter().zip(arg_args.iter()) {
+                        infer_type_args(param_arg, arg_arg, generic_params, classes, bindings);
+                    }
+                }
+            }
+        },
+        (Type::Function(param_params, param_return), Type::Function(arg_params, arg_return)) => {
+            for (param_param, arg_param) in param_params.iter().zip(arg_params.iter()) {
+                infer_type_args(param_param, arg_param, generic_params, classes, bindings);
+            }
+            infer_type_args(param_return, arg_return, generic_params, classes, bindings);
+        },
+        _ => {},
+    }
+}
+
+/// Gives literals without a suffix the type their context expects, so that
+/// `var x as i64 = 5` doesn't have to be written as `var x as i64 = 5i64`.
+fn infer_literal(expr: &mut Spanned<Expr>, expected: &Type) {
+    match &mut expr.0 {
+        Expr::Value(Value::Int(_, typ @ None)) if expected.is_numeric() => *typ = Some(expected.clone()),
+        Expr::Value(Value::Float(_, typ @ None)) if expected.is_float() => *typ = Some(expected.clone()),
+        Expr::Unary(UnaryOp::Neg, a) => infer_literal(a, expected),
+        Expr::Binary(a, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div, b, _) => {
+            infer_literal(a, expected);
+            infer_literal(b, expected);
+        },
+        _ => {},
+    }
+}
+
+/// Whether an expression is a literal whose type depends on its context.
+fn is_untyped_literal(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Value(Value::Int(_, None) | Value::Float(_, None)) => true,
+        Expr::Unary(UnaryOp::Neg, a) => is_untyped_literal(a),
+        _ => false,
+    }
+}
+
+/// The expression a block ends in, which gives the block its value.
+fn tail_of(expr: &mut Spanned<Expr>) -> &mut Spanned<Expr> {
+    match expr.0 {
+        Expr::Block(ref mut body) => tail_of(body),
+        Expr::Then(_, ref mut b) => tail_of(b),
+        _ => expr,
+    }
+}
+
+/// Wraps an already checked expression in a unary op, for the pointers that are taken and followed implicitly.
+fn wrap_unary(op: UnaryOp, expr: &mut Box<Spanned<Expr>>) {
+    let span = expr.1.clone();
+    let inner = std::mem::replace(expr, Box::new((Expr::Error, span.clone())));
+    **expr = (Expr::Unary(op, inner), span);
+}
+
+/// Whether an expression is a literal value, possibly negated.
+fn is_literal(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Value(Value::Bool(_) | Value::Int(..) | Value::Float(..)) => true,
+        Expr::Unary(UnaryOp::Neg, a) => is_literal(a),
+        _ => false,
+    }
+}
+
+/// Whether an expression refers to memory that can be assigned to.
+fn is_place(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Local(_) | Expr::StaticField(..) => true,
+        Expr::Field(obj, _, _) => is_place(obj),
+        Expr::Unary(UnaryOp::Deref, _) => true,
+        _ => false,
+    }
+}
+
+/// Whether every path through the expression ends in a return statement.
+fn always_returns(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Ret(_) => true,
+        Expr::Then(a, b) => always_returns(a) || always_returns(b),
+        Expr::Block(body) => always_returns(body),
+        Expr::If(_cond, a, b) => always_returns(a) && always_returns(b),
+        Expr::Match(_, arms, _) => arms.iter().all(|arm| always_returns(&arm.body)),
+        _ => false,
+    }
+}
+
+/// The most values that a match that doesn't cover every value lists as missing.
+const MAX_MISSING: usize = 3;
+
+/// A pattern as the checks for unreachable patterns and missing values see it. names match anything,
+/// like wildcards do, and bools are matched as the literals 0 and 1
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    Any,
+    Literal(i128),
+    Variant(usize, Vec<Pat>),
+}
+
+/// Converts a pattern that has been checked.
+fn to_pat(pattern: &Spanned<Pattern>) -> Pat {
+    match &pattern.0 {
+        Pattern::Wildcard | Pattern::Binding(_) => Pat::Any,
+        Pattern::Literal(literal) => Pat::Literal(literal_value(literal)),
+        Pattern::Variant(_, fields, tag) => Pat::Variant(tag.unwrap(), fields.iter().map(to_pat).collect()),
+    }
+}
+
+/// The value of an integer or bool literal, possibly negated.
+fn literal_value(expr: &Spanned<Expr>) -> i128 {
+    match &expr.0 {
+        Expr::Value(Value::Int(n, _)) => *n as i128,
+        Expr::Value(Value::Bool(x)) => *x as i128,
+        Expr::Unary(UnaryOp::Neg, a) => -literal_value(a),
+        _ => unreachable!("the type checker only allows integer and bool literals in patterns"),
+    }
+}
+
+/// Whether two patterns start with the same literal or variant.
+fn same_start(a: &Pat, b: &Pat) -> bool {
+    match (a, b) {
+        (Pat::Literal(a), Pat::Literal(b)) => a == b,
+        (Pat::Variant(a, _), Pat::Variant(b, _)) => a == b,
+        _ => false,
+    }
+}
+
+/// The literals and variants that the first patterns of the rows start with, each once.
+fn starts_of(rows: &[Vec<Pat>]) -> Vec<Pat> {
+    let mut starts: Vec<Pat> = Vec::new();
+    for row in rows {
+        if row[0] != Pat::Any && !starts.iter().any(|start| same_start(start, &row[0])) {
+            starts.push(row[0].clone());
+        }
+    }
+    starts
+}
+
+/// The types of the values that a variant holds. literals hold none
+fn held_types(typ: &Type, start: &Pat, unions: &HashMap<String, Union>) -> Vec<Type> {
+    match (typ, start) {
+        (Type::Named(name), Pat::Variant(tag, _)) => unions[name].variants[*tag].payload.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// The rows that match values starting with the given literal or variant, with their first pattern
+/// replaced by the patterns of the values it holds.
+fn specialize(rows: &[Vec<Pat>], start: &Pat, held: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let fields = match &row[0] {
+                Pat::Any => vec![Pat::Any; held],
+                Pat::Variant(_, fields) if same_start(&row[0], start) => fields.clone(),
+                Pat::Literal(_) if same_start(&row[0], start) => Vec::new(),
+                _ => return None,
+            };
+            Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// The rows whose first pattern matches anything, without it.
+fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| row[0] == Pat::Any)
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Whether the given literals and variants are every one that a value of the type can start with.
+/// only bools, unions and integers of up to 16 bits have few enough values to all be written
+fn is_complete(typ: &Type, starts: &[Pat], unions: &HashMap<String, Union>) -> bool {
+    let count = match typ {
+        Type::Bool => 2,
+        Type::Named(name) if unions.contains_key(name) => unions[name].variants.len(),
+        typ => match typ.int_width() {
+            Some((bits, _)) if bits <= 16 => 1 << bits,
+            _ => return false,
+        },
+    };
+
+    starts.len() == count
+}
+
+/// Whether a row of patterns, one for each of the given types, matches any values that none of the rows do.
+fn is_useful(rows: &[Vec<Pat>], row: &[Pat], types: &[Type], unions: &HashMap<String, Union>) -> bool {
+    if row.is_empty() {
+        return rows.is_empty();
+    }
+
+    let starts = match &row[0] {
+        Pat::Any => {
+            let starts = starts_of(rows);
+            if !is_complete(&types[0], &starts, unions) {
+                return is_useful(&default_rows(rows), &row[1..], &types[1..], unions);
+            }
+            starts
+        },
+        start => vec![start.clone()],
+    };
+
+    starts.iter().any(|start| {
+        let held = held_types(&types[0], start, unions);
+        let held_and_rest = [held.clone(), types[1..].to_vec()].concat();
+        let specialized_row = specialize(&[row.to_vec()], start, held.len()).remove(0);
+        is_useful(&specialize(rows, start, held.len()), &specialized_row, &held_and_rest, unions)
+    })
+}
+
+/// Describes a value that a literal or variant starts with, given descriptions of the values it holds, as in Rect(_, 2).
+fn describe_start(typ: &Type, start: &Pat, held: &[String], unions: &HashMap<String, Union>) -> String {
+    match start {
+        Pat::Literal(n) if *typ == Type::Bool => (*n == 1).to_string(),
+        Pat::Literal(n) => n.to_string(),
+        Pat::Variant(tag, _) => {
+            let name = &unions[&typ.to_string()].variants[*tag].name;
+            if held.is_empty() {
+                name.clone()
+            } else {
+                format!("{}({})", name, held.join(", "))
+            }
+        },
+        Pat::Any => "_".to_string(),
+    }
+}
+
+/// Describes values of a type that don't start with any of the given literals and variants.
+/// an integer is described by the one closest to 0
+fn describe_unmatched(typ: &Type, starts: &[Pat], unions: &HashMap<String, Union>) -> Vec<String> {
+    if starts.is_empty() {
+        return vec!["_".to_string()];
+    }
+
+    let unmatched: Vec<Pat> = match typ {
+        Type::Bool => (0..2).map(Pat::Literal).filter(|start| !starts.contains(start)).collect(),
+        Type::Named(name) => (0..unions[name].variants.len())
+            .map(|tag| Pat::Variant(tag, Vec::new()))
+            .filter(|start| !starts.iter().any(|matched| same_start(matched, start)))
+            .collect(),
+        typ => {
+            let (bits, signed) = typ.int_width().unwrap();
+            let (min, max) = if signed { (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1) } else { (0, (1i128 << bits) - 1) };
+            (0..=max).chain((min..0).rev()).map(Pat::Literal).find(|start| !starts.contains(start)).into_iter().collect()
+        },
+    };
+
+    unmatched
+        .iter()
+        .map(|start| {
+            let held = vec!["_".to_string(); held_types(typ, start, unions).len()];
+            describe_start(typ, start, &held, unions)
+        })
+        .collect()
+}
+
+/// Describes values that none of the rows match, with a pattern for each of the given types, as in Rect(_, 2).
+/// at most MAX_MISSING of them are found
+fn missing_cases(rows: &[Vec<Pat>], types: &[Type], unions: &HashMap<String, Union>) -> Vec<Vec<String>> {
+    if types.is_empty() {
+        return if rows.is_empty() { vec![Vec::new()] } else { Vec::new() };
+    }
+
+    let starts = starts_of(rows);
+    let mut missing = Vec::new();
+
+    if is_complete(&types[0], &starts, unions) {
+        for start in starts.iter() {
+            let held = held_types(&types[0], start, unions);
+            let held_count = held.len();
+            let held_and_rest = [held, types[1..].to_vec()].concat();
+
+            for case in missing_cases(&specialize(rows, start, held_count), &held_and_rest, unions) {
+                let (held, rest) = case.split_at(held_count);
+                missing.push([vec![describe_start(&types[0], start, held, unions)], rest.to_vec()].concat());
+            }
+        }
+    } else {
+        for case in missing_cases(&default_rows(rows), &types[1..], unions) {
+            for first in describe_unmatched(&types[0], &starts, unions) {
+                missing.push([vec![first], case.clone()].concat());
+            }
+        }
+    }
+
+    missing.truncate(MAX_MISSING);
+    missing
+}